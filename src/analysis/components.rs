@@ -1,4 +1,4 @@
-use crate::core::graph::Graph;
+use crate::core::view::GraphView;
 use std::collections::HashMap;
 
 struct DisjointSet {
@@ -44,7 +44,7 @@ impl DisjointSet {
     }
 }
 
-pub fn connected_components(graph: &Graph) -> Vec<u32> {
+pub fn connected_components<G: GraphView>(graph: &G) -> Vec<u32> {
     let mut clusters = HashMap::new();
     let mut dsu = DisjointSet::new(graph.node_count());
     for u in 0..graph.node_count() as u32 {
@@ -76,8 +76,8 @@ mod tests {
     #[test]
     fn test_single_component() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 1, 2);
-        gb.add_edge(1, 2, 2, 3);
+        gb.add_edge(0, 1, 1, 2).unwrap();
+        gb.add_edge(1, 2, 2, 3).unwrap();
         let g = gb.freeze();
 
         let cc = connected_components(&g);
@@ -89,8 +89,8 @@ mod tests {
     #[test]
     fn test_two_components() {
         let mut gb = GraphBuilder::new(4);
-        gb.add_edge(0, 1, 1, 2);
-        gb.add_edge(2, 3, 2, 3);
+        gb.add_edge(0, 1, 1, 2).unwrap();
+        gb.add_edge(2, 3, 2, 3).unwrap();
         let g = gb.freeze();
 
         let cc = connected_components(&g);
@@ -112,10 +112,10 @@ mod tests {
     #[test]
     fn test_path_compression() {
         let mut gb = GraphBuilder::new(5);
-        gb.add_edge(0, 1, 0, 0);
-        gb.add_edge(1, 2, 0, 0);
-        gb.add_edge(2, 3, 0, 0);
-        gb.add_edge(3, 4, 0, 0);
+        gb.add_edge(0, 1, 0, 0).unwrap();
+        gb.add_edge(1, 2, 0, 0).unwrap();
+        gb.add_edge(2, 3, 0, 0).unwrap();
+        gb.add_edge(3, 4, 0, 0).unwrap();
         let g = gb.freeze();
 
         let cc = connected_components(&g);
@@ -1,7 +1,7 @@
-use crate::core::graph::Graph;
+use crate::core::view::GraphView;
 use std::mem::swap;
 
-pub fn label_propagation(graph: &Graph, max_iters: usize) -> Vec<u32> {
+pub fn label_propagation<G: GraphView>(graph: &G, max_iters: usize) -> Vec<u32> {
     let mut labels = (0..graph.node_count() as u32).collect::<Vec<u32>>();
     let mut next_labels = vec![0; labels.len()];
     for _ in 0..max_iters {
@@ -32,7 +32,7 @@ pub fn label_propagation(graph: &Graph, max_iters: usize) -> Vec<u32> {
                 }
             }
 
-            if seen_labels.len() > 0 {
+            if !seen_labels.is_empty() {
                 let mut max_idx = 0;
                 new_label = seen_labels[max_idx];
                 for i in 1..seen_labels.len() {
@@ -77,9 +77,9 @@ mod tests {
     #[test]
     fn test_simple_chain() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 2, 3);
-        gb.add_edge(1, 2, 2, 3);
-        gb.add_edge(2, 1, 2, 3);
+        gb.add_edge(0, 1, 2, 3).unwrap();
+        gb.add_edge(1, 2, 2, 3).unwrap();
+        gb.add_edge(2, 1, 2, 3).unwrap();
         let g = gb.freeze();
 
         let expected = vec![2, 1, 2];
@@ -89,17 +89,17 @@ mod tests {
     #[test]
     fn test_dense_groups() {
         let mut gb = GraphBuilder::new(8);
-        gb.add_edge(0, 1, 2, 3);
-        gb.add_edge(1, 2, 2, 3);
-        gb.add_edge(2, 3, 2, 3);
-        gb.add_edge(3, 0, 2, 3);
-        gb.add_edge(3, 1, 2, 3);
-        gb.add_edge(3, 4, 2, 3);
-        gb.add_edge(4, 5, 2, 3);
-        gb.add_edge(5, 6, 2, 3);
-        gb.add_edge(6, 7, 2, 3);
-        gb.add_edge(7, 4, 2, 3);
-        gb.add_edge(7, 5, 2, 3);
+        gb.add_edge(0, 1, 2, 3).unwrap();
+        gb.add_edge(1, 2, 2, 3).unwrap();
+        gb.add_edge(2, 3, 2, 3).unwrap();
+        gb.add_edge(3, 0, 2, 3).unwrap();
+        gb.add_edge(3, 1, 2, 3).unwrap();
+        gb.add_edge(3, 4, 2, 3).unwrap();
+        gb.add_edge(4, 5, 2, 3).unwrap();
+        gb.add_edge(5, 6, 2, 3).unwrap();
+        gb.add_edge(6, 7, 2, 3).unwrap();
+        gb.add_edge(7, 4, 2, 3).unwrap();
+        gb.add_edge(7, 5, 2, 3).unwrap();
         let g = gb.freeze();
 
         let expected = vec![1, 0, 1, 0, 3, 4, 3, 4];
@@ -1,5 +1,6 @@
-use crate::core::graph::Graph;
+use crate::core::assets::AssetScope;
 use crate::core::ids::NodeId;
+use crate::core::view::GraphView;
 
 pub struct DegreeStats {
     pub in_deg: u32,
@@ -11,11 +12,14 @@ pub struct MixerSignal {
     pub node: NodeId,
     pub score: u8,
     pub is_mixer: bool,
+    pub asset: AssetScope,
 }
 
 pub struct MixerConfig {
     pub deg_threshold: u32,
     pub diversity_threshold: u32,
+    /// Window around an incoming transfer in which an outgoing one counts as overlapping, in
+    /// seconds whatever the graph's `TimeUnit`.
     pub window_secs: u64,
 }
 
@@ -29,7 +33,7 @@ impl Default for MixerConfig {
     }
 }
 
-pub fn compute_degree_stats(graph: &Graph) -> Vec<DegreeStats> {
+pub fn compute_degree_stats<G: GraphView>(graph: &G) -> Vec<DegreeStats> {
     let mut result = Vec::with_capacity(graph.node_count());
     for node_id in 0..graph.node_count() {
         result.push(DegreeStats {
@@ -40,10 +44,10 @@ pub fn compute_degree_stats(graph: &Graph) -> Vec<DegreeStats> {
     result
 }
 
-pub fn compute_neighbor_label_diversity(graph: &Graph, labels: &[u32]) -> Vec<u32> {
+pub fn compute_neighbor_label_diversity<G: GraphView>(graph: &G, labels: &[u32]) -> Vec<u32> {
     let mut counts = vec![0; graph.node_count()];
     let mut buf = vec![0; graph.node_count()];
-    for n in 0..graph.node_count() {
+    for (n, count) in counts.iter_mut().enumerate() {
         for label in graph
             .edges_from(n as u32)
             .map(|e| e.dst)
@@ -51,7 +55,7 @@ pub fn compute_neighbor_label_diversity(graph: &Graph, labels: &[u32]) -> Vec<u3
             .map(|node_id| labels[node_id as usize])
         {
             if buf[label as usize] < n + 1 {
-                counts[n] += 1;
+                *count += 1;
                 buf[label as usize] = n + 1;
             }
         }
@@ -59,7 +63,20 @@ pub fn compute_neighbor_label_diversity(graph: &Graph, labels: &[u32]) -> Vec<u3
     counts
 }
 
-pub fn has_in_out_overlap(graph: &Graph, node: NodeId, dt: u64) -> bool {
+pub fn has_in_out_overlap<G: GraphView>(graph: &G, node: NodeId, dt: u64) -> bool {
+    if graph.is_time_sorted() {
+        return graph.edges_to(node).any(|e| {
+            graph
+                .edges_from_between(
+                    node,
+                    e.timestamp.saturating_sub(dt),
+                    e.timestamp.saturating_add(dt),
+                )
+                .next()
+                .is_some()
+        });
+    }
+
     let mut in_time = graph
         .edges_to(node)
         .map(|e| e.timestamp)
@@ -76,12 +93,12 @@ pub fn has_in_out_overlap(graph: &Graph, node: NodeId, dt: u64) -> bool {
     out_time.sort();
 
     let mut from = 0;
-    for i in 0..in_time.len() {
-        for j in from..out_time.len() {
-            if out_time[j].abs_diff(in_time[i]) <= dt {
+    for t_in in &in_time {
+        for (j, t_out) in out_time.iter().enumerate().skip(from) {
+            if t_out.abs_diff(*t_in) <= dt {
                 return true;
             }
-            if out_time[j] > in_time[i] + dt {
+            if *t_out > t_in.saturating_add(dt) {
                 from = j;
                 break;
             }
@@ -91,13 +108,14 @@ pub fn has_in_out_overlap(graph: &Graph, node: NodeId, dt: u64) -> bool {
     false
 }
 
-pub fn detect_mixers(
+pub fn detect_mixers<G: GraphView>(
     cfg: &MixerConfig,
-    graph: &Graph,
+    graph: &G,
     labels: &[u32],
     degree_stats: &[DegreeStats],
 ) -> Vec<MixerSignal> {
-    let diversity = compute_neighbor_label_diversity(graph, &labels);
+    let diversity = compute_neighbor_label_diversity(graph, labels);
+    let window = graph.time_unit().from_secs(cfg.window_secs);
     let mut signals = Vec::with_capacity(graph.node_count());
 
     for n in 0..graph.node_count() {
@@ -108,7 +126,7 @@ pub fn detect_mixers(
         if degree_stats[n].out_deg >= cfg.deg_threshold {
             score += 1;
         }
-        if has_in_out_overlap(graph, n as u32, cfg.window_secs) {
+        if has_in_out_overlap(graph, n as u32, window) {
             score += 1;
         }
         if diversity[n] >= cfg.diversity_threshold {
@@ -118,6 +136,7 @@ pub fn detect_mixers(
             node: n as u32,
             score,
             is_mixer: score >= 3,
+            asset: graph.asset_scope(),
         })
     }
     signals
@@ -126,7 +145,9 @@ pub fn detect_mixers(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::graph::GraphBuilder;
+    use crate::core::graph::{FreezeConfig, GraphBuilder};
+    use crate::core::ids::DEFAULT_ASSET;
+    use crate::core::time::TimeUnit;
     use crate::ingest::synthetic;
     use crate::ingest::synthetic::{
         bridge_node_graph, exchange_hub_graph, normal_user_graph, strong_mixer_graph,
@@ -153,9 +174,9 @@ mod tests {
         assert_eq!(6, stats.len());
         assert_eq!(5, stats[0].in_deg);
         assert_eq!(5, stats[0].out_deg);
-        for i in 1..6 {
-            assert_eq!(1, stats[i].in_deg);
-            assert_eq!(1, stats[i].out_deg);
+        for s in &stats[1..6] {
+            assert_eq!(1, s.in_deg);
+            assert_eq!(1, s.out_deg);
         }
     }
 
@@ -171,8 +192,8 @@ mod tests {
     #[test]
     fn test_single_edge_in_out_overlap() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 2, 10);
-        gb.add_edge(2, 0, 2, 0);
+        gb.add_edge(0, 1, 2, 10).unwrap();
+        gb.add_edge(2, 0, 2, 0).unwrap();
         let g = gb.freeze();
 
         assert!(has_in_out_overlap(&g, 0, 10));
@@ -181,8 +202,8 @@ mod tests {
     #[test]
     fn test_single_edge_no_in_out_overlap() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 2, 11);
-        gb.add_edge(2, 0, 2, 0);
+        gb.add_edge(0, 1, 2, 11).unwrap();
+        gb.add_edge(2, 0, 2, 0).unwrap();
         let g = gb.freeze();
 
         assert!(!has_in_out_overlap(&g, 0, 10));
@@ -191,8 +212,8 @@ mod tests {
     #[test]
     fn test_out_before_in() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 2, 100);
-        gb.add_edge(2, 0, 2, 120);
+        gb.add_edge(0, 1, 2, 100).unwrap();
+        gb.add_edge(2, 0, 2, 120).unwrap();
         let g = gb.freeze();
 
         assert!(has_in_out_overlap(&g, 0, 20));
@@ -201,11 +222,11 @@ mod tests {
     #[test]
     fn test_multiple_edges_single_overlap() {
         let mut gb = GraphBuilder::new(6);
-        gb.add_edge(0, 1, 2, 0);
-        gb.add_edge(0, 2, 2, 1000);
-        gb.add_edge(0, 3, 2, 2000);
-        gb.add_edge(4, 0, 2, 5000);
-        gb.add_edge(5, 0, 2, 1005);
+        gb.add_edge(0, 1, 2, 0).unwrap();
+        gb.add_edge(0, 2, 2, 1000).unwrap();
+        gb.add_edge(0, 3, 2, 2000).unwrap();
+        gb.add_edge(4, 0, 2, 5000).unwrap();
+        gb.add_edge(5, 0, 2, 1005).unwrap();
         let g = gb.freeze();
 
         assert!(has_in_out_overlap(&g, 0, 10));
@@ -214,16 +235,54 @@ mod tests {
     #[test]
     fn test_multiple_edges_no_overlap() {
         let mut gb = GraphBuilder::new(6);
-        gb.add_edge(0, 1, 2, 0);
-        gb.add_edge(0, 2, 2, 1000);
-        gb.add_edge(0, 3, 2, 2000);
-        gb.add_edge(4, 0, 2, 5000);
-        gb.add_edge(5, 0, 2, 3000);
+        gb.add_edge(0, 1, 2, 0).unwrap();
+        gb.add_edge(0, 2, 2, 1000).unwrap();
+        gb.add_edge(0, 3, 2, 2000).unwrap();
+        gb.add_edge(4, 0, 2, 5000).unwrap();
+        gb.add_edge(5, 0, 2, 3000).unwrap();
         let g = gb.freeze();
 
         assert!(!has_in_out_overlap(&g, 0, 100));
     }
 
+    #[test]
+    fn test_time_sorted_in_out_overlap() {
+        let cfg = FreezeConfig {
+            sort_by_time: true,
+            ..Default::default()
+        };
+        let mut gb = GraphBuilder::new(6);
+        gb.add_edge(0, 1, 2, 2000).unwrap();
+        gb.add_edge(0, 2, 2, 0).unwrap();
+        gb.add_edge(0, 3, 2, 1000).unwrap();
+        gb.add_edge(4, 0, 2, 5000).unwrap();
+        gb.add_edge(5, 0, 2, 1005).unwrap();
+        let g = gb.freeze_with(&cfg);
+
+        assert!(has_in_out_overlap(&g, 0, 10));
+        assert!(!has_in_out_overlap(&g, 0, 4));
+        assert!(!has_in_out_overlap(&g, 1, 10));
+    }
+
+    #[test]
+    fn test_in_out_overlap_at_max_window() {
+        for sort_by_time in [false, true] {
+            let cfg = FreezeConfig {
+                sort_by_time,
+                ..Default::default()
+            };
+            let mut gb = GraphBuilder::new(3);
+            gb.add_edge(0, 1, 2, u64::MAX).unwrap();
+            gb.add_edge(2, 0, 2, u64::MAX - 1).unwrap();
+            gb.add_edge(2, 1, 2, 0).unwrap();
+            let g = gb.freeze_with(&cfg);
+
+            assert!(has_in_out_overlap(&g, 0, u64::MAX));
+            assert!(has_in_out_overlap(&g, 0, 1));
+            assert!(!has_in_out_overlap(&g, 2, u64::MAX));
+        }
+    }
+
     #[test]
     fn test_no_edges_label_diversity() {
         let gb = GraphBuilder::new(2);
@@ -235,8 +294,8 @@ mod tests {
     #[test]
     fn test_single_label_neighborhood() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 2, 0);
-        gb.add_edge(0, 2, 2, 0);
+        gb.add_edge(0, 1, 2, 0).unwrap();
+        gb.add_edge(0, 2, 2, 0).unwrap();
         let g = gb.freeze();
 
         let diversity = compute_neighbor_label_diversity(&g, &[0, 1, 1]);
@@ -246,9 +305,9 @@ mod tests {
     #[test]
     fn test_multi_label_neighborhood() {
         let mut gb = GraphBuilder::new(4);
-        gb.add_edge(0, 1, 2, 0);
-        gb.add_edge(0, 2, 2, 0);
-        gb.add_edge(0, 3, 2, 0);
+        gb.add_edge(0, 1, 2, 0).unwrap();
+        gb.add_edge(0, 2, 2, 0).unwrap();
+        gb.add_edge(0, 3, 2, 0).unwrap();
         let g = gb.freeze();
 
         let diversity = compute_neighbor_label_diversity(&g, &[0, 1, 2, 3]);
@@ -258,8 +317,8 @@ mod tests {
     #[test]
     fn test_in_and_out_counted() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 2, 2, 0);
-        gb.add_edge(1, 0, 2, 0);
+        gb.add_edge(0, 2, 2, 0).unwrap();
+        gb.add_edge(1, 0, 2, 0).unwrap();
         let g = gb.freeze();
 
         let diversity = compute_neighbor_label_diversity(&g, &[0, 1, 2]);
@@ -269,8 +328,8 @@ mod tests {
     #[test]
     fn test_duplicate_neighbors_dont_inflate() {
         let mut gb = GraphBuilder::new(2);
-        gb.add_edge(0, 1, 2, 0);
-        gb.add_edge(1, 0, 2, 0);
+        gb.add_edge(0, 1, 2, 0).unwrap();
+        gb.add_edge(1, 0, 2, 0).unwrap();
         let g = gb.freeze();
 
         let diversity = compute_neighbor_label_diversity(&g, &[0, 1]);
@@ -290,7 +349,8 @@ mod tests {
             &MixerSignal {
                 node: 0,
                 score: 0,
-                is_mixer: false
+                is_mixer: false,
+                asset: AssetScope::Single(DEFAULT_ASSET)
             },
             signal
         );
@@ -309,7 +369,8 @@ mod tests {
             &MixerSignal {
                 node: 0,
                 score: 1,
-                is_mixer: false
+                is_mixer: false,
+                asset: AssetScope::Single(DEFAULT_ASSET)
             },
             signal
         );
@@ -328,7 +389,8 @@ mod tests {
             &MixerSignal {
                 node: 0,
                 score: 2,
-                is_mixer: false
+                is_mixer: false,
+                asset: AssetScope::Single(DEFAULT_ASSET)
             },
             signal
         );
@@ -352,9 +414,49 @@ mod tests {
             &MixerSignal {
                 node: 0,
                 score: 4,
-                is_mixer: true
+                is_mixer: true,
+                asset: AssetScope::Single(DEFAULT_ASSET)
             },
             signal
         );
     }
+
+    #[test]
+    fn test_window_in_time_unit() {
+        let score = |unit| {
+            let mut gb = GraphBuilder::new(3);
+            gb.set_time_unit(unit);
+            gb.add_edge(0, 1, 1, 0).unwrap();
+            gb.add_edge(1, 2, 1, 1_800_000).unwrap();
+            let g = gb.freeze();
+            let signals = detect_mixers(
+                &MixerConfig::default(),
+                &g,
+                &[0, 1, 2],
+                &compute_degree_stats(&g),
+            );
+            signals[1].score
+        };
+
+        // half an hour in milliseconds is within the default window of an hour
+        assert_eq!(score(TimeUnit::Seconds) + 1, score(TimeUnit::Milliseconds));
+    }
+
+    #[test]
+    fn test_signal_asset() {
+        let cfg = MixerConfig::default();
+        let mut gb = GraphBuilder::new(3);
+        let usdt = gb.asset_id("USDT").unwrap();
+        gb.add_edge(0, 1, 1, 0).unwrap();
+        gb.add_asset_edge(1, 2, usdt, 1, 0, &[]).unwrap();
+        let g = gb.freeze();
+        let labels = &[0, 1, 2];
+
+        let mixed = detect_mixers(&cfg, &g, labels, &compute_degree_stats(&g));
+        assert!(mixed.iter().all(|m| m.asset == AssetScope::Mixed));
+
+        let view = g.asset_view(usdt);
+        let signals = detect_mixers(&cfg, &view, labels, &compute_degree_stats(&view));
+        assert!(signals.iter().all(|m| m.asset == AssetScope::Single(usdt)));
+    }
 }
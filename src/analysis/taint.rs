@@ -1,12 +1,26 @@
-use crate::core::graph::Graph;
+use crate::core::amount;
+use crate::core::assets::AssetScope;
 use crate::core::ids::NodeId;
+use crate::core::view::GraphView;
+use anyhow::Context;
 use std::collections::{HashMap, VecDeque};
 
 const INITIAL_RISK: f32 = 1.0;
 const DECAY: f32 = 0.5;
 const EPSILON: f32 = 1e-6;
 
-pub fn propagate(graph: &Graph, start: NodeId, max_hops: usize) -> HashMap<NodeId, f32> {
+#[derive(Debug)]
+pub struct TaintResult {
+    pub asset: AssetScope,
+    pub risk: HashMap<NodeId, f32>,
+}
+
+/// Fails if the outgoing amounts of a reached node overflow an `Amount` when summed.
+pub fn propagate<G: GraphView>(
+    graph: &G,
+    start: NodeId,
+    max_hops: usize,
+) -> anyhow::Result<TaintResult> {
     let mut risk_map = HashMap::from([(start, INITIAL_RISK)]);
     let mut visited = VecDeque::from([(start, INITIAL_RISK, 0, None)]);
 
@@ -16,15 +30,16 @@ pub fn propagate(graph: &Graph, start: NodeId, max_hops: usize) -> HashMap<NodeI
             continue;
         }
 
-        let total_amount = graph.edges_from(node).map(|e| e.amount).sum::<u64>();
+        let total_amount = amount::checked_sum(graph.edges_from(node).map(|e| e.amount))
+            .with_context(|| format!("outgoing amounts of node {node} overflow"))?;
         if total_amount == 0 {
             continue;
         }
         for edge in graph.edges_from(node) {
             let mut edge_risk = new_risk;
-            edge_risk *= edge.amount as f32 / total_amount as f32;
+            edge_risk *= (edge.amount as f64 / total_amount as f64) as f32;
             edge_risk *= if let Some(ts) = last_ts {
-                let dt = edge.timestamp.saturating_sub(ts);
+                let dt = graph.time_unit().to_secs(edge.timestamp.saturating_sub(ts));
                 1.0 / ((1.0 + dt as f32) / (60 * 60 * 24) as f32)
             } else {
                 1.0
@@ -44,31 +59,37 @@ pub fn propagate(graph: &Graph, start: NodeId, max_hops: usize) -> HashMap<NodeI
         }
     }
 
-    risk_map
+    Ok(TaintResult {
+        asset: graph.asset_scope(),
+        risk: risk_map,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::amount::Amount;
     use crate::core::graph::GraphBuilder;
+    use crate::core::time::TimeUnit;
+    use crate::core::view::MinAmount;
     use approx::assert_relative_eq;
 
     #[test]
     fn test_no_hops() {
         let mut gb = GraphBuilder::new(2);
-        gb.add_edge(0, 1, 2, 3);
+        gb.add_edge(0, 1, 2, 3).unwrap();
         let g = gb.freeze();
 
-        assert_eq!(HashMap::from([(0, 1.0)]), propagate(&g, 0, 0));
+        assert_eq!(HashMap::from([(0, 1.0)]), propagate(&g, 0, 0).unwrap().risk);
     }
 
     #[test]
     fn test_single_edge() {
         let mut gb = GraphBuilder::new(2);
-        gb.add_edge(0, 1, 2, 3);
+        gb.add_edge(0, 1, 2, 3).unwrap();
         let g = gb.freeze();
 
-        let actual = propagate(&g, 0, 1);
+        let actual = propagate(&g, 0, 1).unwrap().risk;
         assert_eq!(2, actual.len());
         assert_relative_eq!(1.0f32, actual.get(&0).unwrap());
         assert!(*actual.get(&1).unwrap() < 1.0)
@@ -77,54 +98,54 @@ mod tests {
     #[test]
     fn test_hop_limit_enforced() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 2, 3);
-        gb.add_edge(1, 2, 2, 3);
+        gb.add_edge(0, 1, 2, 3).unwrap();
+        gb.add_edge(1, 2, 2, 3).unwrap();
         let g = gb.freeze();
 
-        let actual = propagate(&g, 0, 1);
+        let actual = propagate(&g, 0, 1).unwrap().risk;
         assert_eq!(2, actual.len());
-        assert!(actual.get(&0).is_some());
-        assert!(actual.get(&1).is_some());
+        assert!(actual.contains_key(&0));
+        assert!(actual.contains_key(&1));
     }
 
     #[test]
     fn test_simple_cycle() {
         let mut gb = GraphBuilder::new(2);
-        gb.add_edge(0, 1, 2, 3);
-        gb.add_edge(1, 0, 2, 3);
+        gb.add_edge(0, 1, 2, 3).unwrap();
+        gb.add_edge(1, 0, 2, 3).unwrap();
         let g = gb.freeze();
 
-        let actual = propagate(&g, 0, 10);
+        let actual = propagate(&g, 0, 10).unwrap().risk;
         assert_eq!(2, actual.len());
-        assert!(actual.get(&0).is_some());
-        assert!(actual.get(&1).is_some());
+        assert!(actual.contains_key(&0));
+        assert!(actual.contains_key(&1));
     }
 
     #[test]
     fn test_multiple_paths() {
         let mut gb = GraphBuilder::new(4);
-        gb.add_edge(0, 1, 2, 3);
-        gb.add_edge(1, 3, 2, 3);
-        gb.add_edge(0, 2, 2, 3);
-        gb.add_edge(2, 3, 2, 3);
+        gb.add_edge(0, 1, 2, 3).unwrap();
+        gb.add_edge(1, 3, 2, 3).unwrap();
+        gb.add_edge(0, 2, 2, 3).unwrap();
+        gb.add_edge(2, 3, 2, 3).unwrap();
         let g = gb.freeze();
 
-        let actual = propagate(&g, 0, 10);
+        let actual = propagate(&g, 0, 10).unwrap().risk;
         assert_eq!(4, actual.len());
-        assert!(actual.get(&0).is_some());
-        assert!(actual.get(&1).is_some());
-        assert!(actual.get(&2).is_some());
-        assert!(actual.get(&3).is_some());
+        assert!(actual.contains_key(&0));
+        assert!(actual.contains_key(&1));
+        assert!(actual.contains_key(&2));
+        assert!(actual.contains_key(&3));
     }
 
     #[test]
     fn test_fan_out_dilution() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 100, 3);
-        gb.add_edge(0, 2, 1, 3);
+        gb.add_edge(0, 1, 100, 3).unwrap();
+        gb.add_edge(0, 2, 1, 3).unwrap();
         let g = gb.freeze();
 
-        let actual = propagate(&g, 0, 1);
+        let actual = propagate(&g, 0, 1).unwrap().risk;
         assert_eq!(3, actual.len());
         assert!(actual.get(&1).unwrap() > actual.get(&2).unwrap());
     }
@@ -132,23 +153,34 @@ mod tests {
     #[test]
     fn test_zero_amounts() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 0, 3);
-        gb.add_edge(0, 2, 0, 3);
+        gb.add_edge(0, 1, 0, 3).unwrap();
+        gb.add_edge(0, 2, 0, 3).unwrap();
         let g = gb.freeze();
 
-        let actual = propagate(&g, 0, 1);
+        let actual = propagate(&g, 0, 1).unwrap().risk;
         assert_eq!(1, actual.len());
-        assert!(actual.get(&0).is_some());
+        assert!(actual.contains_key(&0));
+    }
+
+    #[test]
+    fn test_amounts_overflowing_sum() {
+        let mut gb = GraphBuilder::new(3);
+        gb.add_edge(0, 1, Amount::MAX, 3).unwrap();
+        gb.add_edge(0, 2, Amount::MAX, 3).unwrap();
+        let g = gb.freeze();
+
+        assert!(propagate(&g, 0, 1).is_err());
+        assert_eq!(1, propagate(&g, 0, 0).unwrap().risk.len());
     }
 
     #[test]
     fn test_first_hop_unaffected_by_ts() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 1, 1, 3);
-        gb.add_edge(0, 2, 4, 3);
+        gb.add_edge(0, 1, 1, 3).unwrap();
+        gb.add_edge(0, 2, 4, 3).unwrap();
         let g = gb.freeze();
 
-        let actual = propagate(&g, 0, 1);
+        let actual = propagate(&g, 0, 1).unwrap().risk;
         assert_eq!(3, actual.len());
         assert_relative_eq!(0.5 * (1f32 / 5f32), actual.get(&1).unwrap());
     }
@@ -156,13 +188,13 @@ mod tests {
     #[test]
     fn test_short_vs_long_ts_gap() {
         let mut gb = GraphBuilder::new(5);
-        gb.add_edge(0, 1, 1, 10);
-        gb.add_edge(0, 2, 1, 10);
-        gb.add_edge(1, 3, 1, 10);
-        gb.add_edge(2, 4, 1, 20);
+        gb.add_edge(0, 1, 1, 10).unwrap();
+        gb.add_edge(0, 2, 1, 10).unwrap();
+        gb.add_edge(1, 3, 1, 10).unwrap();
+        gb.add_edge(2, 4, 1, 20).unwrap();
         let g = gb.freeze();
 
-        let actual = propagate(&g, 0, 2);
+        let actual = propagate(&g, 0, 2).unwrap().risk;
         assert_eq!(5, actual.len());
         assert!(actual.get(&3).unwrap() > actual.get(&4).unwrap());
     }
@@ -170,13 +202,57 @@ mod tests {
     #[test]
     fn test_large_ts_gap_pruned() {
         let mut gb = GraphBuilder::new(5);
-        gb.add_edge(0, 1, 1, 10);
-        gb.add_edge(0, 2, 1, 10);
-        gb.add_edge(1, 3, 1, 60 * 60 * 24 * 100000);
-        gb.add_edge(1, 4, 1, 10);
+        gb.add_edge(0, 1, 1, 10).unwrap();
+        gb.add_edge(0, 2, 1, 10).unwrap();
+        gb.add_edge(1, 3, 1, 60 * 60 * 24 * 100000).unwrap();
+        gb.add_edge(1, 4, 1, 10).unwrap();
         let g = gb.freeze();
 
-        let actual = propagate(&g, 0, 2);
+        let actual = propagate(&g, 0, 2).unwrap().risk;
         assert_eq!(4, actual.len());
     }
+
+    #[test]
+    fn test_ts_gap_in_time_unit() {
+        let risk = |unit, gap| {
+            let mut gb = GraphBuilder::new(3);
+            gb.set_time_unit(unit);
+            gb.add_edge(0, 1, 1, 0).unwrap();
+            gb.add_edge(1, 2, 1, gap).unwrap();
+            propagate(&gb.freeze(), 0, 2).unwrap().risk[&2]
+        };
+
+        let seconds = risk(TimeUnit::Seconds, 7200);
+        assert_relative_eq!(seconds, risk(TimeUnit::Milliseconds, 7_200_000));
+        assert_relative_eq!(seconds, risk(TimeUnit::Blocks { block_secs: 600 }, 12));
+        assert!(seconds < risk(TimeUnit::Seconds, 60));
+    }
+
+    #[test]
+    fn test_result_asset() {
+        let mut gb = GraphBuilder::new(3);
+        let eth = gb.asset_id("ETH").unwrap();
+        let usdt = gb.asset_id("USDT").unwrap();
+        gb.add_asset_edge(0, 1, eth, 1, 3, &[]).unwrap();
+        gb.add_asset_edge(0, 2, usdt, 1000, 3, &[]).unwrap();
+        let g = gb.freeze();
+
+        assert_eq!(AssetScope::Mixed, propagate(&g, 0, 1).unwrap().asset);
+        let actual = propagate(&g.asset_view(eth), 0, 1).unwrap();
+        assert_eq!(AssetScope::Single(eth), actual.asset);
+        assert_eq!(2, actual.risk.len());
+        assert_relative_eq!(0.5f32, actual.risk.get(&1).unwrap());
+    }
+
+    #[test]
+    fn test_filtered_view() {
+        let mut gb = GraphBuilder::new(3);
+        gb.add_edge(0, 1, 100, 3).unwrap();
+        gb.add_edge(0, 2, 1, 3).unwrap();
+        let g = gb.freeze();
+
+        let actual = propagate(&MinAmount::new(&g, 10), 0, 1).unwrap().risk;
+        assert_eq!(2, actual.len());
+        assert_relative_eq!(0.5f32, actual.get(&1).unwrap());
+    }
 }
@@ -0,0 +1,259 @@
+use crate::core::column::{Column, valid_offsets};
+use crate::core::ids::NodeId;
+use anyhow::ensure;
+use std::ops::Range;
+
+/// Neighbour ids and timestamps of the edges in one traversal direction, grouped by node.
+pub(crate) enum Adjacency {
+    Plain {
+        nodes: Column<NodeId>,
+        timestamps: Column<u64>,
+    },
+    // Every node's edges as interleaved varints of zigzag encoded deltas, neighbour first and
+    // timestamp second, both relative to the previous edge of the node; `offsets` holds the
+    // byte range of every node.
+    Compressed {
+        offsets: Column<u64>,
+        data: Column<u8>,
+    },
+}
+
+impl Adjacency {
+    pub(crate) fn compress(nodes: &[NodeId], timestamps: &[u64], edge_offsets: &[usize]) -> Self {
+        let mut offsets = Vec::with_capacity(edge_offsets.len());
+        let mut data = Vec::new();
+        offsets.push(0);
+        for range in edge_offsets.windows(2) {
+            let (mut node, mut ts) = (0u64, 0u64);
+            for e in range[0]..range[1] {
+                write_varint(&mut data, zigzag(nodes[e] as u64, node));
+                write_varint(&mut data, zigzag(timestamps[e], ts));
+                node = nodes[e] as u64;
+                ts = timestamps[e];
+            }
+            offsets.push(data.len() as u64);
+        }
+        data.shrink_to_fit();
+        Adjacency::Compressed {
+            offsets: offsets.into(),
+            data: data.into(),
+        }
+    }
+
+    pub(crate) fn is_compressed(&self) -> bool {
+        matches!(self, Adjacency::Compressed { .. })
+    }
+
+    /// Timestamps of all edges by position, only available without compression.
+    pub(crate) fn timestamps(&self) -> Option<&[u64]> {
+        match self {
+            Adjacency::Plain { timestamps, .. } => Some(timestamps),
+            Adjacency::Compressed { .. } => None,
+        }
+    }
+
+    /// Bytes used by the neighbour ids and timestamps.
+    pub(crate) fn size_bytes(&self) -> usize {
+        match self {
+            Adjacency::Plain { nodes, timestamps } => {
+                size_of_val::<[NodeId]>(nodes) + size_of_val::<[u64]>(timestamps)
+            }
+            Adjacency::Compressed { offsets, data } => size_of_val::<[u64]>(offsets) + data.len(),
+        }
+    }
+
+    /// Edges of `node` in `range`, which has to be a part of the node's edge range
+    /// `node_start..` in the offsets of the graph.
+    pub(crate) fn iter(
+        &self,
+        node: NodeId,
+        node_start: usize,
+        range: Range<usize>,
+    ) -> AdjacencyIter<'_> {
+        match self {
+            Adjacency::Plain { nodes, timestamps } => AdjacencyIter::Plain {
+                nodes: &nodes[range.clone()],
+                timestamps: &timestamps[range],
+                next: 0,
+            },
+            Adjacency::Compressed { offsets, data } => {
+                let from = offsets[node as usize] as usize;
+                let to = offsets[node as usize + 1] as usize;
+                let mut iter = AdjacencyIter::Compressed {
+                    data: data.get(from..to).unwrap_or(&[]),
+                    remaining: range.end - node_start,
+                    node: 0,
+                    timestamp: 0,
+                };
+                for _ in node_start..range.start {
+                    iter.next();
+                }
+                iter
+            }
+        }
+    }
+
+    pub(crate) fn validate(&self, node_count: usize) -> anyhow::Result<()> {
+        if let Adjacency::Compressed { offsets, data } = self {
+            ensure!(
+                offsets.len() == node_count + 1
+                    && valid_offsets(offsets.iter().copied(), data.len()),
+                "compressed adjacency is corrupted"
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether every neighbour id is below `node_count`, given the edge range of every node in
+    /// `edge_offsets`. A compressed node also has to decode to exactly its edges.
+    pub(crate) fn valid_nodes(&self, edge_offsets: &[usize], node_count: usize) -> bool {
+        match self {
+            Adjacency::Plain { nodes, .. } => nodes.iter().all(|n| (*n as usize) < node_count),
+            Adjacency::Compressed { offsets, data } => {
+                edge_offsets.windows(2).enumerate().all(|(n, range)| {
+                    let mut bytes = &data[offsets[n] as usize..offsets[n + 1] as usize];
+                    let mut node = 0;
+                    for _ in range[0]..range[1] {
+                        let Some(delta) = read_varint(&mut bytes) else {
+                            return false;
+                        };
+                        node = unzigzag(delta, node);
+                        if node >= node_count as u64 || read_varint(&mut bytes).is_none() {
+                            return false;
+                        }
+                    }
+                    bytes.is_empty()
+                })
+            }
+        }
+    }
+}
+
+pub(crate) enum AdjacencyIter<'a> {
+    Plain {
+        nodes: &'a [NodeId],
+        timestamps: &'a [u64],
+        next: usize,
+    },
+    Compressed {
+        data: &'a [u8],
+        remaining: usize,
+        node: u64,
+        timestamp: u64,
+    },
+}
+
+impl AdjacencyIter<'_> {
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            AdjacencyIter::Plain { nodes, next, .. } => nodes.len() - next,
+            AdjacencyIter::Compressed { remaining, .. } => *remaining,
+        }
+    }
+}
+
+impl Iterator for AdjacencyIter<'_> {
+    type Item = (NodeId, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            AdjacencyIter::Plain {
+                nodes,
+                timestamps,
+                next,
+            } => {
+                let item = (*nodes.get(*next)?, timestamps[*next]);
+                *next += 1;
+                Some(item)
+            }
+            AdjacencyIter::Compressed {
+                data,
+                remaining,
+                node,
+                timestamp,
+            } => {
+                if *remaining == 0 {
+                    return None;
+                }
+                *node = unzigzag(read_varint(data)?, *node);
+                *timestamp = unzigzag(read_varint(data)?, *timestamp);
+                *remaining -= 1;
+                Some((*node as NodeId, *timestamp))
+            }
+        }
+    }
+}
+
+// Deltas wrap around so that any pair of values round trips.
+fn zigzag(value: u64, prev: u64) -> u64 {
+    let delta = value.wrapping_sub(prev) as i64;
+    ((delta << 1) ^ (delta >> 63)) as u64
+}
+
+fn unzigzag(encoded: u64, prev: u64) -> u64 {
+    let delta = ((encoded >> 1) as i64) ^ -((encoded & 1) as i64);
+    prev.wrapping_add(delta as u64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+// Returns `None` on truncated input, which only happens with a corrupted snapshot.
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = data.split_first()?;
+        *data = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_round_trip() {
+        let values = [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX];
+        for prev in values {
+            for value in values {
+                let mut buf = vec![];
+                write_varint(&mut buf, zigzag(value, prev));
+                let mut data = &buf[..];
+                assert_eq!(
+                    Some(value),
+                    read_varint(&mut data).map(|v| unzigzag(v, prev))
+                );
+                assert!(data.is_empty());
+            }
+        }
+        assert_eq!(None, read_varint(&mut &[0x80][..]));
+    }
+
+    #[test]
+    fn test_compressed_iter() {
+        let nodes = [5, 1, u32::MAX, 7];
+        let timestamps = [100, 90, 1_700_000_000, 0];
+        let offsets = [0, 3, 3, 4];
+        let adj = Adjacency::compress(&nodes, &timestamps, &offsets);
+
+        let all = adj.iter(0, 0, 0..3).collect::<Vec<_>>();
+        assert_eq!(vec![(5, 100), (1, 90), (u32::MAX, 1_700_000_000)], all);
+        assert_eq!(0, adj.iter(1, 3, 3..3).count());
+        assert_eq!(vec![(7, 0)], adj.iter(2, 3, 3..4).collect::<Vec<_>>());
+
+        let mut window = adj.iter(0, 0, 1..2);
+        assert_eq!(1, window.len());
+        assert_eq!(Some((1, 90)), window.next());
+        assert_eq!(None, window.next());
+    }
+}
@@ -0,0 +1,316 @@
+use crate::core::amount::Amount;
+use crate::core::attributes::{AttributeColumn, AttributeValue};
+use crate::core::column::{Column, valid_offsets};
+use crate::core::graph::{FreezeConfig, Graph, GraphBuilder};
+use crate::core::ids::EdgeId;
+use crate::core::parallel;
+use anyhow::Context;
+use std::collections::HashMap;
+
+/// Summary of the transfers collapsed into an aggregated edge; the edge's amount is their
+/// total and its timestamp the first one.
+#[derive(Debug, PartialEq)]
+pub struct EdgeAggregate {
+    pub count: u64,
+    pub last_timestamp: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Transfer<'a> {
+    pub amount: Amount,
+    pub timestamp: u64,
+    /// Values of the graph's attributes, in registration order.
+    pub attributes: Vec<AttributeValue<'a>>,
+}
+
+// Indexed by the id of the aggregated edge; its transfers are at
+// `transfer_offsets[e]..transfer_offsets[e + 1]` of the transfer columns.
+pub(crate) struct Aggregates {
+    pub(crate) last_timestamps: Column<u64>,
+    pub(crate) transfer_offsets: Column<u64>,
+    pub(crate) transfer_amounts: Column<Amount>,
+    pub(crate) transfer_timestamps: Column<u64>,
+    // raw values of every attribute per transfer, strings index into the table of the graph's
+    // attribute column
+    pub(crate) transfer_attributes: Vec<Column<u64>>,
+}
+
+impl Aggregates {
+    pub(crate) fn validate(
+        &self,
+        edge_count: usize,
+        attributes: &[AttributeColumn],
+    ) -> anyhow::Result<()> {
+        let transfer_count = self.transfer_amounts.len();
+        anyhow::ensure!(
+            self.last_timestamps.len() == edge_count
+                && self.transfer_offsets.len() == edge_count + 1
+                && valid_offsets(self.transfer_offsets.iter().copied(), transfer_count)
+                && self.transfer_timestamps.len() == transfer_count
+                && self.transfer_attributes.len() == attributes.len()
+                && self
+                    .transfer_attributes
+                    .iter()
+                    .zip(attributes)
+                    .all(|(values, attr)| {
+                        values.len() == transfer_count && attr.valid_values(values)
+                    }),
+            "aggregated transfers are corrupted"
+        );
+        Ok(())
+    }
+}
+
+// Replaces the builder's edges with one edge per source, destination and asset, which takes the
+// attribute values of its first transfer; every transfer keeps its own values. The aggregated
+// edges are already in the order freeze lays them out in, by source and then first timestamp or
+// first occurrence, so the returned columns line up with the edge ids of the frozen graph. Fails
+// if a total does not fit into an `Amount`, leaving the builder as it was.
+pub(crate) fn collapse(gb: &mut GraphBuilder, cfg: &FreezeConfig) -> anyhow::Result<Aggregates> {
+    let mut groups = HashMap::new();
+    let mut group_of = Vec::with_capacity(gb.srcs.len());
+    let mut firsts = vec![];
+    for e in 0..gb.srcs.len() {
+        let key = (gb.srcs[e], gb.dsts[e], gb.assets[e]);
+        let group = *groups.entry(key).or_insert(firsts.len());
+        if group == firsts.len() {
+            firsts.push(e);
+        }
+        group_of.push(group);
+    }
+    drop(groups);
+
+    let mut counts = vec![0usize; firsts.len()];
+    let mut totals = vec![0 as Amount; firsts.len()];
+    let mut first_timestamps = vec![u64::MAX; firsts.len()];
+    let mut last_timestamps = vec![0; firsts.len()];
+    for (e, group) in group_of.iter().enumerate() {
+        counts[*group] += 1;
+        totals[*group] = totals[*group].checked_add(gb.amounts[e]).with_context(|| {
+            format!(
+                "total amount of the transfers from node {} to {} overflows",
+                gb.srcs[e], gb.dsts[e]
+            )
+        })?;
+        first_timestamps[*group] = first_timestamps[*group].min(gb.timestamps[e]);
+        last_timestamps[*group] = last_timestamps[*group].max(gb.timestamps[e]);
+    }
+
+    let mut order = (0..firsts.len()).collect::<Vec<_>>();
+    if cfg.sort_by_time {
+        order.sort_by_key(|g| (gb.srcs[firsts[*g]], first_timestamps[*g]));
+    } else {
+        order.sort_by_key(|g| gb.srcs[firsts[*g]]);
+    }
+    let mut rank = vec![0; order.len()];
+    for (r, g) in order.iter().enumerate() {
+        rank[*g] = r;
+    }
+
+    let mut transfer_offsets = vec![0; order.len() + 1];
+    for (r, g) in order.iter().enumerate() {
+        transfer_offsets[r + 1] = transfer_offsets[r] + counts[*g];
+    }
+    let mut cursor = transfer_offsets.clone();
+    let mut transfers = vec![0; group_of.len()];
+    for (e, group) in group_of.iter().enumerate() {
+        transfers[cursor[rank[*group]]] = e;
+        cursor[rank[*group]] += 1;
+    }
+    if cfg.sort_by_time {
+        for range in transfer_offsets.windows(2) {
+            transfers[range[0]..range[1]].sort_by_key(|e| gb.timestamps[*e]);
+        }
+    }
+
+    let threads = cfg.threads.max(1);
+    let aggregates = Aggregates {
+        last_timestamps: order
+            .iter()
+            .map(|g| last_timestamps[*g])
+            .collect::<Vec<_>>()
+            .into(),
+        transfer_offsets: transfer_offsets
+            .iter()
+            .map(|o| *o as u64)
+            .collect::<Vec<_>>()
+            .into(),
+        transfer_amounts: parallel::gather(&gb.amounts, &transfers, threads).into(),
+        transfer_timestamps: parallel::gather(&gb.timestamps, &transfers, threads).into(),
+        transfer_attributes: gb
+            .attributes
+            .iter()
+            .map(|attr| parallel::gather(&attr.values, &transfers, threads).into())
+            .collect(),
+    };
+
+    let picks = order.iter().map(|g| firsts[*g]).collect::<Vec<_>>();
+    gb.srcs = parallel::gather(&gb.srcs, &picks, threads);
+    gb.dsts = parallel::gather(&gb.dsts, &picks, threads);
+    gb.assets = parallel::gather(&gb.assets, &picks, threads);
+    gb.amounts = order.iter().map(|g| totals[*g]).collect();
+    gb.timestamps = order.iter().map(|g| first_timestamps[*g]).collect();
+    for attr in &mut gb.attributes {
+        attr.values = parallel::gather(&attr.values, &picks, threads);
+    }
+    Ok(aggregates)
+}
+
+impl Graph {
+    pub fn is_aggregated(&self) -> bool {
+        self.aggregates.is_some()
+    }
+
+    /// `None` if the graph was frozen without `FreezeConfig::aggregate_parallel`.
+    pub fn aggregate(&self, edge: EdgeId) -> Option<EdgeAggregate> {
+        let aggregates = self.aggregates.as_ref()?;
+        let e = edge as usize;
+        Some(EdgeAggregate {
+            count: aggregates.transfer_offsets[e + 1] - aggregates.transfer_offsets[e],
+            last_timestamp: aggregates.last_timestamps[e],
+        })
+    }
+
+    /// The raw transfers collapsed into an aggregated edge with their own attribute values, in
+    /// insertion order or by timestamp for a time sorted graph; `None` if the graph is not
+    /// aggregated.
+    pub fn transfers(&self, edge: EdgeId) -> Option<impl Iterator<Item = Transfer<'_>> + '_> {
+        let aggregates = self.aggregates.as_ref()?;
+        let from = aggregates.transfer_offsets[edge as usize] as usize;
+        let to = aggregates.transfer_offsets[edge as usize + 1] as usize;
+        Some((from..to).map(|t| {
+            Transfer {
+                amount: aggregates.transfer_amounts[t],
+                timestamp: aggregates.transfer_timestamps[t],
+                attributes: self
+                    .attributes
+                    .iter()
+                    .zip(&aggregates.transfer_attributes)
+                    .map(|(attr, values)| attr.resolve(values[t]))
+                    .collect(),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::attributes::{AttributeKind, AttributeValue};
+    use crate::core::graph::OutgoingEdgeRef;
+
+    fn builder() -> GraphBuilder {
+        let mut gb = GraphBuilder::new(3);
+        let eth = gb.asset_id("ETH").unwrap();
+        gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+        for (src, dst, asset, amount, ts, tx) in [
+            (0, 1, 0, 5, 30, "a"),
+            (1, 2, 0, 1, 10, "b"),
+            (0, 1, 0, 7, 10, "c"),
+            (0, 2, 0, 2, 20, "d"),
+            (0, 1, eth, 9, 40, "e"),
+            (0, 1, 0, 3, 50, "f"),
+        ] {
+            gb.add_asset_edge(src, dst, asset, amount, ts, &[AttributeValue::Str(tx)])
+                .unwrap();
+        }
+        gb
+    }
+
+    #[test]
+    fn test_aggregate_parallel_edges() {
+        let g = builder()
+            .try_freeze_with(&FreezeConfig {
+                aggregate_parallel: true,
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert!(g.is_aggregated());
+        assert_eq!(4, g.edge_count());
+        let edges = g.edges_from(0).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                OutgoingEdgeRef::new(0, 1, 15, 10),
+                OutgoingEdgeRef::new(1, 2, 2, 20),
+                OutgoingEdgeRef::new(2, 1, 9, 40),
+            ],
+            edges
+        );
+        assert_eq!(
+            Some(EdgeAggregate {
+                count: 3,
+                last_timestamp: 50
+            }),
+            g.aggregate(0)
+        );
+        let amounts = g
+            .transfers(0)
+            .unwrap()
+            .map(|t| t.amount)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![5, 7, 3], amounts);
+        let tx = g.attribute_id("tx_hash").unwrap();
+        assert_eq!(AttributeValue::Str("a"), g.attribute(tx, 0));
+        let hashes = g
+            .transfers(0)
+            .unwrap()
+            .map(|t| t.attributes[tx])
+            .collect::<Vec<_>>();
+        assert_eq!(["a", "c", "f"].map(AttributeValue::Str).to_vec(), hashes);
+        assert_eq!(Some("ETH"), g.asset_name(g.edge_asset(2)));
+        assert_eq!(
+            vec![15, 9],
+            g.edges_to(1).map(|e| e.amount).collect::<Vec<_>>()
+        );
+
+        let raw = builder().freeze();
+        assert!(raw.aggregate(0).is_none());
+        assert!(raw.transfers(0).is_none());
+    }
+
+    #[test]
+    fn test_aggregate_overflow() {
+        let mut gb = GraphBuilder::new(2);
+        gb.add_edge(0, 1, Amount::MAX, 1).unwrap();
+        gb.add_edge(0, 1, 1, 2).unwrap();
+        let error = gb
+            .try_freeze_with(&FreezeConfig {
+                aggregate_parallel: true,
+                ..Default::default()
+            })
+            .err()
+            .unwrap();
+        assert_eq!(
+            "total amount of the transfers from node 0 to 1 overflows",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_aggregate_time_sorted() {
+        for threads in [1, 3] {
+            let g = builder()
+                .try_freeze_with(&FreezeConfig {
+                    sort_by_time: true,
+                    aggregate_parallel: true,
+                    threads,
+                    ..Default::default()
+                })
+                .unwrap();
+
+            let edges = g
+                .edges_from(0)
+                .map(|e| (e.dst, e.timestamp))
+                .collect::<Vec<_>>();
+            assert_eq!(vec![(1, 10), (2, 20), (1, 40)], edges);
+            let timestamps = g
+                .transfers(0)
+                .unwrap()
+                .map(|t| t.timestamp)
+                .collect::<Vec<_>>();
+            assert_eq!(vec![10, 30, 50], timestamps);
+            assert_eq!(1, g.aggregate(3).unwrap().count);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+/// Quantity in base units of an edge's asset, wide enough for 18 decimal token amounts.
+pub type Amount = u128;
+
+/// Largest decimal scale of an asset, one unit of it still fits into an `Amount`.
+pub const MAX_DECIMALS: u8 = 38;
+
+/// Why a decimal amount could not be read by `parse_amount`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AmountError {
+    Malformed,
+    /// More significant fractional digits than the asset has decimals.
+    PrecisionLoss,
+    /// Too many base units for an `Amount`.
+    Overflow,
+}
+
+/// Sum of `amounts`, `None` on overflow.
+pub fn checked_sum(amounts: impl IntoIterator<Item = Amount>) -> Option<Amount> {
+    amounts.into_iter().try_fold(0, Amount::checked_add)
+}
+
+/// Base units of a decimal number with up to `decimals` fractional digits. Commas may group the
+/// integer digits by thousands, e.g. `"1,234.5"`.
+pub fn parse_amount(text: &str, decimals: u8) -> Result<Amount, AmountError> {
+    let (int, frac) = text.split_once('.').unwrap_or((text, ""));
+    let groups = int.split(',').collect::<Vec<_>>();
+    let grouped = groups.len() == 1
+        || (1..=3).contains(&groups[0].len()) && groups[1..].iter().all(|g| g.len() == 3);
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if int.len() + frac.len() == 0 || !grouped || !groups.iter().all(|g| digits(g)) || !digits(frac)
+    {
+        return Err(AmountError::Malformed);
+    }
+    let frac = frac.trim_end_matches('0');
+    if frac.len() > decimals as usize {
+        return Err(AmountError::PrecisionLoss);
+    }
+
+    let mut scaled = groups
+        .iter()
+        .flat_map(|g| g.bytes())
+        .chain(frac.bytes())
+        .chain(std::iter::repeat_n(b'0', decimals as usize - frac.len()));
+    scaled.try_fold(0, |amount: Amount, digit| {
+        amount
+            .checked_mul(10)
+            .and_then(|a| a.checked_add((digit - b'0') as Amount))
+            .ok_or(AmountError::Overflow)
+    })
+}
+
+/// `amount * rate` rounded half up, computed exactly in integers from the binary value of
+/// `rate`. `None` if the rate is negative or not finite, or the product overflows an `Amount`.
+pub fn checked_mul_rate(amount: Amount, rate: f64) -> Option<Amount> {
+    if !rate.is_finite() || rate < 0.0 {
+        return None;
+    }
+    // rate = mantissa * 2^exp
+    let bits = rate.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    let (mantissa, exp) = match biased {
+        0 => (fraction, -1074),
+        _ => (fraction | 1 << 52, biased - 1075),
+    };
+
+    // 192 bit product as hi * 2^128 + lo
+    let low = (amount as u64 as u128) * mantissa as u128;
+    let high = (amount >> 64) * mantissa as u128;
+    let (lo, carry) = low.overflowing_add(high << 64);
+    let hi = (high >> 64) + carry as u128;
+
+    if exp >= 0 {
+        let shift = exp as u32;
+        return (hi == 0 && lo.leading_zeros() >= shift).then(|| lo << shift);
+    }
+    let shift = exp.unsigned_abs();
+    let bit = |i: u32| match i {
+        0..128 => lo >> i & 1,
+        128..256 => hi >> (i - 128) & 1,
+        _ => 0,
+    };
+    let (quotient_hi, quotient) = match shift {
+        0..128 => (hi >> shift, lo >> shift | hi << (128 - shift)),
+        128..256 => (0, hi >> (shift - 128)),
+        _ => (0, 0),
+    };
+    if quotient_hi != 0 {
+        return None;
+    }
+    quotient.checked_add(bit(shift - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sums() {
+        assert_eq!(Some(6), checked_sum([1, 2, 3]));
+        assert_eq!(None, checked_sum([Amount::MAX, 1]));
+        assert_eq!(Some(0), checked_sum([]));
+    }
+
+    #[test]
+    fn test_checked_mul_rate() {
+        assert_eq!(Some(2000), checked_mul_rate(2, 1000.0));
+        assert_eq!(Some(0), checked_mul_rate(300, 0.0));
+        assert_eq!(Some(0), checked_mul_rate(300, -0.0));
+        assert_eq!(Some(2), checked_mul_rate(3, 0.5));
+        assert_eq!(Some(1), checked_mul_rate(1, 0.5));
+        assert_eq!(Some(0), checked_mul_rate(1, 0.25));
+        assert_eq!(Some(0), checked_mul_rate(Amount::MAX, f64::MIN_POSITIVE));
+        assert_eq!(Some(Amount::MAX), checked_mul_rate(Amount::MAX, 1.0));
+        assert_eq!(Some(1 << 127), checked_mul_rate(Amount::MAX, 0.5));
+        assert_eq!(
+            Some(1_000_000_000_000_000_000_000_000_000_000_000_000),
+            checked_mul_rate(1_000_000_000_000_000_000_000_000, 1e12)
+        );
+        // the exact product of an amount beyond f64 precision
+        assert_eq!(Some((1 << 100) + 1), checked_mul_rate((1 << 101) + 2, 0.5));
+        assert_eq!(None, checked_mul_rate(Amount::MAX, 2.0));
+        assert_eq!(None, checked_mul_rate(1 << 64, 1e30));
+        assert_eq!(None, checked_mul_rate(1, -1.0));
+        assert_eq!(None, checked_mul_rate(1, f64::NAN));
+        assert_eq!(None, checked_mul_rate(1, f64::INFINITY));
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(Ok(1500), parse_amount("1.5", 3));
+        assert_eq!(Ok(1_340_000), parse_amount("0.0134", 8));
+        assert_eq!(Ok(123_400), parse_amount("1,234.00", 2));
+        assert_eq!(Ok(42), parse_amount("42", 0));
+        assert_eq!(Ok(500), parse_amount(".5", 3));
+        assert_eq!(Ok(Amount::MAX), parse_amount(&Amount::MAX.to_string(), 0));
+        assert_eq!(Err(AmountError::PrecisionLoss), parse_amount("1.5", 0));
+        assert_eq!(Err(AmountError::PrecisionLoss), parse_amount("0.123", 2));
+        assert_eq!(
+            Err(AmountError::Overflow),
+            parse_amount("340282366920938463464", 18)
+        );
+        for malformed in [
+            "", ".", "-1", "1e3", "1.2.3", "12,34", "1,234,5", ",123", "1.5,0",
+        ] {
+            assert_eq!(
+                Err(AmountError::Malformed),
+                parse_amount(malformed, 8),
+                "{malformed}"
+            );
+        }
+    }
+}
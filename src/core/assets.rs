@@ -0,0 +1,245 @@
+use crate::core::amount::{self, MAX_DECIMALS};
+use crate::core::graph::Graph;
+use crate::core::ids::AssetId;
+use crate::core::view::AssetFilter;
+use anyhow::{bail, ensure};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AssetScope {
+    /// All amounts are in base units of one asset.
+    Single(AssetId),
+    /// Amounts of different assets share the graph and are not comparable.
+    Mixed,
+    /// Amounts were converted to a common unit of value.
+    Normalized,
+}
+
+// Names and decimal scales of the assets known to a builder, indexed by `AssetId`. Id 0 is the
+// unnamed default asset.
+#[derive(Clone)]
+pub(crate) struct AssetTable {
+    pub(crate) names: Vec<String>,
+    pub(crate) decimals: Vec<u8>,
+    // whether the decimals of an asset were set rather than defaulted to 0
+    pub(crate) scaled: Vec<bool>,
+    ids: HashMap<String, AssetId>,
+}
+
+impl Default for AssetTable {
+    fn default() -> Self {
+        Self::from_parts(vec![String::new()], vec![0], vec![false])
+    }
+}
+
+impl AssetTable {
+    // Table of the assets of a frozen graph, keeping their ids, decimals and whether these were
+    // set.
+    pub(crate) fn from_parts(names: Vec<String>, decimals: Vec<u8>, scaled: Vec<bool>) -> Self {
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(asset, name)| (name.clone(), asset as AssetId))
+            .collect();
+        Self {
+            names,
+            decimals,
+            scaled,
+            ids,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<AssetId> {
+        self.ids.get(name).copied()
+    }
+
+    // Decimals of `asset`, `None` if they were never set.
+    pub(crate) fn scale(&self, asset: AssetId) -> Option<u8> {
+        let asset = asset as usize;
+        self.scaled
+            .get(asset)
+            .is_some_and(|&scaled| scaled)
+            .then(|| self.decimals[asset])
+    }
+
+    // Id of the asset `name`, registered with no decimals if it is new.
+    pub(crate) fn id(&mut self, name: &str) -> anyhow::Result<AssetId> {
+        if let Some(asset) = self.get(name) {
+            return Ok(asset);
+        }
+        ensure!(
+            self.names.len() <= AssetId::MAX as usize,
+            "asset {name} exceeds the limit of {} assets",
+            AssetId::MAX as usize + 1
+        );
+        let asset = self.names.len() as AssetId;
+        self.names.push(name.to_string());
+        self.decimals.push(0);
+        self.scaled.push(false);
+        self.ids.insert(name.to_string(), asset);
+        Ok(asset)
+    }
+
+    pub(crate) fn set_decimals(&mut self, asset: AssetId, decimals: u8) -> anyhow::Result<()> {
+        ensure!(
+            decimals <= MAX_DECIMALS,
+            "asset scale of {decimals} decimals exceeds the limit of {MAX_DECIMALS}"
+        );
+        let Some(scale) = self.decimals.get_mut(asset as usize) else {
+            bail!("unknown asset {asset}");
+        };
+        *scale = decimals;
+        self.scaled[asset as usize] = true;
+        Ok(())
+    }
+
+    // Heap bytes of the names, counted twice for the index keys.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        let names = self.names.iter().map(|n| n.capacity()).sum::<usize>();
+        2 * names
+            + self.names.capacity() * size_of::<String>()
+            + self.decimals.capacity()
+            + self.ids.capacity() * (size_of::<String>() + size_of::<AssetId>())
+    }
+}
+
+impl Graph {
+    // Table of the graph's assets, for a builder of a graph derived from it.
+    pub(crate) fn asset_table(&self) -> AssetTable {
+        AssetTable::from_parts(
+            self.asset_names.clone(),
+            self.asset_decimals.clone(),
+            self.asset_scaled.clone(),
+        )
+    }
+
+    /// The edges of `asset`, filtered lazily with the node and edge ids of the graph.
+    pub fn asset_view(&self, asset: AssetId) -> AssetFilter<'_> {
+        AssetFilter::new(self, asset)
+    }
+
+    /// Copy of the graph with amounts converted to a common unit; `rates[asset]` is the value
+    /// of one base unit of the asset. Amounts are multiplied exactly and rounded half up, a
+    /// converted amount that overflows is an error. The copy is a new CSR of all edges, as
+    /// large as the graph itself.
+    pub fn normalized_copy(&self, rates: &[f64]) -> anyhow::Result<Graph> {
+        ensure!(
+            rates.len() >= self.asset_names.len(),
+            "expected {} asset rates, got {}",
+            self.asset_names.len(),
+            rates.len()
+        );
+        for (asset, rate) in rates.iter().enumerate() {
+            ensure!(
+                rate.is_finite() && *rate >= 0.0,
+                "rate {rate} of asset {asset} is not a non-negative number"
+            );
+        }
+        let mut overflow = None;
+        let mut graph = self.rebuild(self.node_count(), |src, e| {
+            let rate = rates[self.edge_asset(e.id) as usize];
+            match amount::checked_mul_rate(e.amount, rate) {
+                Some(amount) => Some((src, e.dst, amount)),
+                None => {
+                    overflow.get_or_insert(e.id);
+                    None
+                }
+            }
+        });
+        if let Some(edge) = overflow {
+            bail!("normalized amount of edge {edge} overflows");
+        }
+        graph.asset_scope = AssetScope::Normalized;
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::attributes::{AttributeKind, AttributeValue};
+    use crate::core::graph::GraphBuilder;
+    use crate::core::ids::DEFAULT_ASSET;
+    use crate::core::view::GraphView;
+
+    fn sample() -> Graph {
+        let mut gb = GraphBuilder::new(3);
+        let eth = gb.asset_id("ETH").unwrap();
+        let usdt = gb.asset_id("USDT").unwrap();
+        gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+        gb.add_asset_edge(0, 1, eth, 2, 10, &[AttributeValue::Str("a")])
+            .unwrap();
+        gb.add_asset_edge(0, 2, usdt, 300, 20, &[AttributeValue::Str("b")])
+            .unwrap();
+        gb.add_asset_edge(1, 2, eth, 1, 30, &[AttributeValue::Str("c")])
+            .unwrap();
+        gb.freeze()
+    }
+
+    #[test]
+    fn test_scope() {
+        let mut gb = GraphBuilder::new(2);
+        gb.add_edge(0, 1, 1, 1).unwrap();
+        assert_eq!(AssetScope::Single(DEFAULT_ASSET), gb.freeze().asset_scope());
+
+        let g = sample();
+        assert_eq!(AssetScope::Mixed, g.asset_scope());
+        assert_eq!(Some(1), g.asset_id("ETH"));
+        assert_eq!(Some("USDT"), g.asset_name(2));
+        assert_eq!(None, g.asset_id("BTC"));
+    }
+
+    #[test]
+    fn test_asset_table() {
+        let mut table = AssetTable::default();
+        assert_eq!(Some(DEFAULT_ASSET), table.get(""));
+        assert_eq!(None, table.scale(DEFAULT_ASSET));
+        for asset in 1..=AssetId::MAX {
+            assert_eq!(asset, table.id(&asset.to_string()).unwrap());
+        }
+        assert_eq!(1, table.id("1").unwrap());
+        assert!(table.id("x").is_err());
+        assert_eq!(AssetId::MAX as usize + 1, table.len());
+        assert!(table.set_decimals(1, MAX_DECIMALS + 1).is_err());
+        assert!(table.set_decimals(AssetId::MAX, 6).is_ok());
+        assert_eq!((None, Some(6)), (table.scale(1), table.scale(AssetId::MAX)));
+    }
+
+    #[test]
+    fn test_asset_view() {
+        let g = sample();
+        let eth = g.asset_id("ETH").unwrap();
+        let view = g.asset_view(eth);
+
+        assert_eq!(AssetScope::Single(eth), view.asset_scope());
+        assert_eq!(3, view.node_count());
+        let edges = (0..3).map(|n| view.edges_from(n).count()).sum::<usize>();
+        assert_eq!(2, edges);
+        assert_eq!(1, view.edges_from_between(0, 0, 10).count());
+        assert_eq!(0, view.edges_to(2).filter(|e| e.src == 0).count());
+        let tx = g.attribute_id("tx_hash").unwrap();
+        let hashes = view
+            .edges_to(2)
+            .map(|e| g.attribute(tx, e.id))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![AttributeValue::Str("c")], hashes);
+    }
+
+    #[test]
+    fn test_normalized_copy() {
+        let g = sample();
+        let view = g.normalized_copy(&[0.0, 1000.0, 1.0]).unwrap();
+
+        assert_eq!(AssetScope::Normalized, view.asset_scope());
+        let amounts = view.edges_from(0).map(|e| e.amount).collect::<Vec<_>>();
+        assert_eq!(vec![2000, 300], amounts);
+        assert!(g.normalized_copy(&[1.0]).is_err());
+        assert!(g.normalized_copy(&[0.0, f64::NAN, 1.0]).is_err());
+        assert!(g.normalized_copy(&[0.0, 1.0, -1.0]).is_err());
+        assert!(g.normalized_copy(&[0.0, 1.0, 1e38]).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::core::column::{Column, valid_offsets};
+use anyhow::{bail, ensure};
+
+/// Index of an attribute column, in registration order.
+pub type AttributeId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttributeKind {
+    U64,
+    Str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AttributeValue<'a> {
+    U64(u64),
+    Str(&'a str),
+}
+
+impl AttributeValue<'_> {
+    pub fn kind(&self) -> AttributeKind {
+        match self {
+            AttributeValue::U64(_) => AttributeKind::U64,
+            AttributeValue::Str(_) => AttributeKind::Str,
+        }
+    }
+}
+
+// Per-edge values are kept as u64 for both kinds so the column can be permuted along with the
+// other edge arrays during freeze; for `Str` the value indexes into the string table.
+pub(crate) struct AttributeBuilder {
+    pub(crate) name: String,
+    pub(crate) kind: AttributeKind,
+    pub(crate) values: Vec<u64>,
+    str_offsets: Vec<u64>,
+    str_data: Vec<u8>,
+}
+
+impl AttributeBuilder {
+    pub(crate) fn new(name: &str, kind: AttributeKind, edge_count: usize) -> Self {
+        let mut attr = Self {
+            name: name.to_string(),
+            kind,
+            values: Vec::with_capacity(edge_count),
+            str_offsets: vec![0],
+            str_data: vec![],
+        };
+        for _ in 0..edge_count {
+            attr.push_default();
+        }
+        attr
+    }
+
+    pub(crate) fn check(&self, value: &AttributeValue) -> anyhow::Result<()> {
+        ensure!(
+            value.kind() == self.kind,
+            "attribute {} expects {:?} values, got {:?}",
+            self.name,
+            self.kind,
+            value.kind()
+        );
+        Ok(())
+    }
+
+    pub(crate) fn push(&mut self, value: &AttributeValue) {
+        match value {
+            AttributeValue::U64(v) => self.values.push(*v),
+            AttributeValue::Str(s) => {
+                self.values.push(self.str_offsets.len() as u64 - 1);
+                self.str_data.extend_from_slice(s.as_bytes());
+                self.str_offsets.push(self.str_data.len() as u64);
+            }
+        }
+    }
+
+    pub(crate) fn push_default(&mut self) {
+        match self.kind {
+            AttributeKind::U64 => self.push(&AttributeValue::U64(0)),
+            AttributeKind::Str => self.push(&AttributeValue::Str("")),
+        }
+    }
+
+    pub(crate) fn heap_bytes(&self) -> usize {
+        size_of::<u64>() * (self.values.capacity() + self.str_offsets.capacity())
+            + self.str_data.capacity()
+    }
+
+    pub(crate) fn freeze(self) -> AttributeColumn {
+        AttributeColumn {
+            name: self.name,
+            kind: self.kind,
+            values: self.values.into(),
+            str_offsets: self.str_offsets.into(),
+            str_data: self.str_data.into(),
+        }
+    }
+}
+
+/// Attribute values of a frozen graph, indexed by `EdgeId`.
+pub struct AttributeColumn {
+    pub(crate) name: String,
+    pub(crate) kind: AttributeKind,
+    pub(crate) values: Column<u64>,
+    pub(crate) str_offsets: Column<u64>,
+    pub(crate) str_data: Column<u8>,
+}
+
+impl AttributeColumn {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> AttributeKind {
+        self.kind
+    }
+
+    pub fn get(&self, idx: usize) -> AttributeValue<'_> {
+        self.resolve(self.values[idx])
+    }
+
+    // Value of a raw per-edge value, see `AttributeBuilder`.
+    pub(crate) fn resolve(&self, value: u64) -> AttributeValue<'_> {
+        match self.kind {
+            AttributeKind::U64 => AttributeValue::U64(value),
+            AttributeKind::Str => {
+                let from = self.str_offsets[value as usize] as usize;
+                let to = self.str_offsets[value as usize + 1] as usize;
+                let text = std::str::from_utf8(&self.str_data[from..to]);
+                AttributeValue::Str(text.expect("strings are validated when loaded"))
+            }
+        }
+    }
+
+    pub(crate) fn validate(&self, edge_count: usize) -> anyhow::Result<()> {
+        ensure!(
+            self.values.len() == edge_count,
+            "attribute {} has {} values, expected {edge_count}",
+            self.name,
+            self.values.len()
+        );
+        // strings of a snapshot are checked here once instead of on every read
+        ensure!(
+            valid_offsets(self.str_offsets.iter().copied(), self.str_data.len())
+                && self.str_offsets.windows(2).all(|w| {
+                    std::str::from_utf8(&self.str_data[w[0] as usize..w[1] as usize]).is_ok()
+                })
+                && self.valid_values(&self.values),
+            "attribute {} string table is corrupted",
+            self.name
+        );
+        Ok(())
+    }
+
+    // Whether every raw value resolves, which a string does if it indexes the string table.
+    pub(crate) fn valid_values(&self, values: &[u64]) -> bool {
+        let strings = self.str_offsets.len().saturating_sub(1) as u64;
+        self.kind == AttributeKind::U64 || values.iter().all(|v| *v < strings)
+    }
+}
+
+pub(crate) fn kind_to_u8(kind: AttributeKind) -> u8 {
+    match kind {
+        AttributeKind::U64 => 0,
+        AttributeKind::Str => 1,
+    }
+}
+
+pub(crate) fn kind_from_u8(kind: u8) -> anyhow::Result<AttributeKind> {
+    match kind {
+        0 => Ok(AttributeKind::U64),
+        1 => Ok(AttributeKind::Str),
+        _ => bail!("unknown attribute kind {kind}"),
+    }
+}
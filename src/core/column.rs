@@ -0,0 +1,109 @@
+use bytemuck::Pod;
+use memmap2::Mmap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Read-only array backing a frozen `Graph`, either owned or served from a memory-mapped file.
+pub enum Column<T> {
+    Owned(Vec<T>),
+    Mapped(MappedSlice<T>),
+}
+
+pub struct MappedSlice<T> {
+    map: Arc<Mmap>,
+    offset: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> MappedSlice<T> {
+    /// Returns `None` if the range is out of bounds or not aligned for `T`.
+    pub fn new(map: Arc<Mmap>, offset: usize, len: usize) -> Option<Self> {
+        let end = len.checked_mul(size_of::<T>())?.checked_add(offset)?;
+        bytemuck::try_cast_slice::<u8, T>(map.get(offset..end)?).ok()?;
+        Some(Self {
+            map,
+            offset,
+            len,
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Whether `offsets` start at zero and ascend to `end`, so that every range between two of
+/// them can be sliced from a column of `end` elements.
+pub(crate) fn valid_offsets(offsets: impl IntoIterator<Item = u64>, end: usize) -> bool {
+    let mut offsets = offsets.into_iter();
+    let Some(0) = offsets.next() else {
+        return false;
+    };
+    let mut prev = 0;
+    for offset in offsets {
+        if offset < prev {
+            return false;
+        }
+        prev = offset;
+    }
+    prev == end as u64
+}
+
+impl<T> Column<T> {
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, Column::Mapped(_))
+    }
+
+    /// Allocated bytes of an owned column, zero for a mapped one.
+    pub fn heap_bytes(&self) -> usize {
+        match self {
+            Column::Owned(values) => values.capacity() * size_of::<T>(),
+            Column::Mapped(_) => 0,
+        }
+    }
+
+    /// Bytes of the file served by a mapped column, zero for an owned one.
+    pub fn mapped_bytes(&self) -> usize {
+        match self {
+            Column::Owned(_) => 0,
+            Column::Mapped(slice) => slice.len * size_of::<T>(),
+        }
+    }
+}
+
+impl<T: Pod> Deref for Column<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Column::Owned(values) => values,
+            Column::Mapped(slice) => bytemuck::cast_slice(
+                &slice.map[slice.offset..slice.offset + slice.len * size_of::<T>()],
+            ),
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for Column<T> {
+    fn from(values: Vec<T>) -> Self {
+        Column::Owned(values)
+    }
+}
+
+impl<T: Pod + fmt::Debug> fmt::Debug for Column<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Pod + PartialEq> PartialEq for Column<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Pod + PartialEq> PartialEq<Column<T>> for Vec<T> {
+    fn eq(&self, other: &Column<T>) -> bool {
+        self[..] == **other
+    }
+}
@@ -0,0 +1,279 @@
+use crate::core::aggregate::Transfer;
+use crate::core::assets::AssetScope;
+use crate::core::graph::{FreezeConfig, Graph, GraphBuilder};
+use crate::core::ids::{NodeId, NodeRegistry};
+use anyhow::{bail, ensure};
+use std::collections::{HashMap, HashSet};
+
+/// Id of an entity, which is also its node id in the contracted graph.
+pub type EntityId = NodeId;
+
+/// Assignment of every node to an entity, e.g. the addresses controlled by one exchange.
+/// Entities are numbered in the order of their first member node.
+pub struct EntityMap {
+    entity_of: Vec<EntityId>,
+    // CSR index of the member nodes of every entity
+    member_offsets: Vec<usize>,
+    members: Vec<NodeId>,
+    /// Entity names, their ids are the entity ids.
+    pub names: NodeRegistry,
+}
+
+impl EntityMap {
+    fn new(entity_of: Vec<EntityId>, names: NodeRegistry) -> Self {
+        let mut member_offsets = vec![0; names.len() + 1];
+        for entity in &entity_of {
+            member_offsets[*entity as usize + 1] += 1;
+        }
+        for i in 1..member_offsets.len() {
+            member_offsets[i] += member_offsets[i - 1];
+        }
+        let mut cursors = member_offsets.clone();
+        let mut members = vec![0; entity_of.len()];
+        for (node, entity) in entity_of.iter().enumerate() {
+            members[cursors[*entity as usize]] = node as NodeId;
+            cursors[*entity as usize] += 1;
+        }
+        Self {
+            entity_of,
+            member_offsets,
+            members,
+            names,
+        }
+    }
+
+    /// Entities of an attribution, pairs of a node and the name of its entity. Nodes without
+    /// an attribution are an entity of their own, named by their external id. Attributing a
+    /// node to two different entities, or to an entity named like an unattributed node, is an
+    /// error.
+    pub fn from_attribution<'a>(
+        registry: &NodeRegistry,
+        attribution: impl IntoIterator<Item = (NodeId, &'a str)>,
+    ) -> anyhow::Result<Self> {
+        let mut attributed = vec![None; registry.len()];
+        for (node, name) in attribution {
+            ensure!(
+                (node as usize) < registry.len(),
+                "node {node} is not in the registry"
+            );
+            match attributed[node as usize] {
+                Some(other) if other != name => bail!(
+                    "{} is attributed to both {other} and {name}",
+                    registry.external_id(node)
+                ),
+                _ => attributed[node as usize] = Some(name),
+            }
+        }
+        let entity_names = attributed.iter().flatten().copied().collect::<HashSet<_>>();
+        let mut names = NodeRegistry::new();
+        let mut entity_of = Vec::with_capacity(registry.len());
+        for (node, id) in registry.iter() {
+            let name = match attributed[node as usize] {
+                Some(name) => name,
+                None if entity_names.contains(id) => {
+                    bail!("unattributed address {id} has the name of an entity")
+                }
+                None => id,
+            };
+            entity_of.push(names.get_or_insert(name));
+        }
+        Ok(Self::new(entity_of, names))
+    }
+
+    /// Entities of a clustering, e.g. the labels of `label_propagation`, one per distinct label
+    /// and named by the external id of its first member.
+    pub fn from_labels(registry: &NodeRegistry, labels: &[u32]) -> anyhow::Result<Self> {
+        ensure!(
+            labels.len() == registry.len(),
+            "expected {} labels, got {}",
+            registry.len(),
+            labels.len()
+        );
+        let mut names = NodeRegistry::new();
+        let mut entities = HashMap::new();
+        let entity_of = registry
+            .iter()
+            .map(|(node, id)| {
+                *entities
+                    .entry(labels[node as usize])
+                    .or_insert_with(|| names.get_or_insert(id))
+            })
+            .collect();
+        Ok(Self::new(entity_of, names))
+    }
+
+    pub fn entity_of(&self, node: NodeId) -> EntityId {
+        self.entity_of[node as usize]
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Member nodes of `entity` in ascending order.
+    pub fn members(&self, entity: EntityId) -> impl Iterator<Item = NodeId> + '_ {
+        let entity = entity as usize;
+        self.members[self.member_offsets[entity]..self.member_offsets[entity + 1]]
+            .iter()
+            .copied()
+    }
+}
+
+impl Graph {
+    /// Entity level graph with one node per entity and one aggregated edge per source entity,
+    /// destination entity and asset, see `Graph::transfers`. Transfers between nodes of the
+    /// same entity are dropped. Aggregated edges are expanded into their transfers first.
+    pub fn contract(&self, entities: &EntityMap) -> anyhow::Result<Graph> {
+        ensure!(
+            entities.entity_of.len() == self.node_count(),
+            "graph has {} nodes but the entity map {}",
+            self.node_count(),
+            entities.entity_of.len()
+        );
+        let mut gb = GraphBuilder::new(entities.entity_count());
+        gb.asset_table = self.asset_table();
+        gb.time_unit = self.time_unit;
+        for attr in &self.attributes {
+            gb.add_attribute(attr.name(), attr.kind())?;
+        }
+        for src in 0..self.node_count() as NodeId {
+            let src_entity = entities.entity_of(src);
+            for e in self.edges_from(src) {
+                let dst_entity = entities.entity_of(e.dst);
+                if src_entity == dst_entity {
+                    continue;
+                }
+                let transfers = match self.transfers(e.id) {
+                    Some(transfers) => transfers.collect(),
+                    None => vec![Transfer {
+                        amount: e.amount,
+                        timestamp: e.timestamp,
+                        attributes: self
+                            .attributes
+                            .iter()
+                            .map(|a| a.get(e.id as usize))
+                            .collect(),
+                    }],
+                };
+                for transfer in transfers {
+                    gb.add_asset_edge(
+                        src_entity,
+                        dst_entity,
+                        self.edge_asset(e.id),
+                        transfer.amount,
+                        transfer.timestamp,
+                        &transfer.attributes,
+                    )?;
+                }
+            }
+        }
+        let mut graph = gb.try_freeze_with(&FreezeConfig {
+            sort_by_time: self.time_sorted,
+            compress: self.is_compressed(),
+            aggregate_parallel: true,
+            ..Default::default()
+        })?;
+        if self.asset_scope == AssetScope::Normalized {
+            graph.asset_scope = AssetScope::Normalized;
+        }
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::taint;
+
+    // a1 and a2 belong to exchange A, b1 and b2 to B, c is unattributed
+    fn sample() -> (Graph, NodeRegistry) {
+        let mut registry = NodeRegistry::new();
+        let mut gb = GraphBuilder::growable();
+        for (src, dst, amount, ts) in [
+            ("a1", "b1", 5, 1),
+            ("a2", "b2", 7, 2),
+            ("a1", "a2", 100, 3),
+            ("b1", "c", 4, 4),
+            ("a2", "b1", 1, 5),
+        ] {
+            let src = registry.get_or_insert(src);
+            let dst = registry.get_or_insert(dst);
+            gb.add_edge(src, dst, amount, ts).unwrap();
+        }
+        gb.reserve_nodes(registry.len());
+        (gb.freeze(), registry)
+    }
+
+    fn attribution(registry: &NodeRegistry) -> EntityMap {
+        let pairs = [("a1", "A"), ("a2", "A"), ("b1", "B"), ("b2", "B")]
+            .map(|(address, name)| (registry.get(address).unwrap(), name));
+        EntityMap::from_attribution(registry, pairs).unwrap()
+    }
+
+    #[test]
+    fn test_from_attribution() {
+        let (_, registry) = sample();
+        let entities = attribution(&registry);
+
+        assert_eq!(3, entities.entity_count());
+        let [a, b, c] = ["A", "B", "c"].map(|name| entities.names.get(name).unwrap());
+        assert_eq!((0, 1, 2), (a, b, c));
+        assert_eq!(b, entities.entity_of(registry.get("b2").unwrap()));
+        assert_eq!(vec![0, 2], entities.members(a).collect::<Vec<_>>());
+        assert_eq!(vec![4], entities.members(c).collect::<Vec<_>>());
+
+        let conflicting = [(0, "A"), (0, "B")];
+        assert!(EntityMap::from_attribution(&registry, conflicting).is_err());
+        let colliding = [(0, "c")];
+        assert!(EntityMap::from_attribution(&registry, colliding).is_err());
+    }
+
+    #[test]
+    fn test_from_labels() {
+        let (_, registry) = sample();
+        let entities = EntityMap::from_labels(&registry, &[7, 3, 7, 3, 9]).unwrap();
+
+        assert_eq!(3, entities.entity_count());
+        assert_eq!(
+            vec!["a1", "b1", "c"],
+            entities
+                .names
+                .iter()
+                .map(|(_, name)| name)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, entities.entity_of(3));
+        assert_eq!(vec![1, 3], entities.members(1).collect::<Vec<_>>());
+        assert!(EntityMap::from_labels(&registry, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_contract() {
+        let (g, registry) = sample();
+        let entities = attribution(&registry);
+        let contracted = g.contract(&entities).unwrap();
+
+        assert_eq!(3, contracted.node_count());
+        assert_eq!(2, contracted.edge_count());
+        let e = contracted.edges_from(0).next().unwrap();
+        assert_eq!((1, 13, 1), (e.dst, e.amount, e.timestamp));
+        assert_eq!(3, contracted.aggregate(e.id).unwrap().count);
+        assert_eq!(
+            vec![5, 7, 1],
+            contracted
+                .transfers(e.id)
+                .unwrap()
+                .map(|t| t.amount)
+                .collect::<Vec<_>>()
+        );
+
+        let risk = taint::propagate(&contracted, entities.entity_of(0), 2)
+            .unwrap()
+            .risk;
+        assert!(risk.contains_key(&2));
+        assert!(
+            g.contract(&EntityMap::from_labels(&NodeRegistry::new(), &[]).unwrap())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,559 @@
+use crate::core::amount::Amount;
+use crate::core::assets::{AssetScope, AssetTable};
+use crate::core::ids::{AssetId, DEFAULT_ASSET, EdgeId, NodeId, NodeRegistry};
+use crate::core::snapshot::{self, ColumnFiles, SpilledGraph};
+use crate::core::time::TimeUnit;
+use anyhow::ensure;
+use bytemuck::{Pod, Zeroable};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub struct ExternalConfig {
+    /// Edges buffered in memory before a sorted run is written to disk, 48 bytes each.
+    pub run_edges: usize,
+    /// Runs merged at once, each with an open file and a read buffer; more runs are merged in
+    /// several passes.
+    pub merge_fan_in: usize,
+    /// Same as `FreezeConfig::sort_by_time`.
+    pub sort_by_time: bool,
+}
+
+impl Default for ExternalConfig {
+    fn default() -> Self {
+        Self {
+            run_edges: 1 << 22,
+            merge_fan_in: 64,
+            sort_by_time: false,
+        }
+    }
+}
+
+/// Builds a graph whose size is bounded by disk rather than memory: edges are spilled to sorted
+/// runs in a scratch directory as they arrive and merged into a snapshot by `finish`, which can
+/// then be opened with `load_snapshot` or `map_snapshot`. Only per-node counts are kept in memory.
+///
+/// The output is identical to a snapshot of `GraphBuilder::freeze_with` with the same edges and
+/// sort order. Attributes, compression and aggregation are not supported on this path.
+pub struct ExternalBuilder {
+    dir: PathBuf,
+    node_count: usize,
+    edge_count: usize,
+    asset_table: AssetTable,
+    time_unit: TimeUnit,
+    sort_by_time: bool,
+    out_runs: RunSorter<OutRecord>,
+}
+
+impl ExternalBuilder {
+    /// Uses `dir` for the runs and column files, creating it if needed; they are removed by
+    /// `finish`, or when the builder is dropped.
+    pub fn new(dir: impl AsRef<Path>, cfg: &ExternalConfig) -> anyhow::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            out_runs: RunSorter::new(&dir, "out", cfg),
+            dir,
+            node_count: 0,
+            edge_count: 0,
+            asset_table: AssetTable::default(),
+            time_unit: TimeUnit::Seconds,
+            sort_by_time: cfg.sort_by_time,
+        })
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    /// See `GraphBuilder::reserve_nodes`.
+    pub fn reserve_nodes(&mut self, node_count: usize) {
+        self.node_count = self.node_count.max(node_count);
+    }
+
+    /// See `GraphBuilder::asset_id`.
+    pub fn asset_id(&mut self, name: &str) -> anyhow::Result<AssetId> {
+        self.asset_table.id(name)
+    }
+
+    /// See `GraphBuilder::set_asset_decimals`.
+    pub fn set_asset_decimals(&mut self, asset: AssetId, decimals: u8) -> anyhow::Result<()> {
+        self.asset_table.set_decimals(asset, decimals)
+    }
+
+    /// See `GraphBuilder::set_time_unit`.
+    pub fn set_time_unit(&mut self, unit: TimeUnit) {
+        self.time_unit = unit;
+    }
+
+    pub fn add_edge(
+        &mut self,
+        src: NodeId,
+        dst: NodeId,
+        amount: Amount,
+        timestamp: u64,
+    ) -> anyhow::Result<()> {
+        self.add_asset_edge(src, dst, DEFAULT_ASSET, amount, timestamp)
+    }
+
+    pub fn add_asset_edge(
+        &mut self,
+        src: NodeId,
+        dst: NodeId,
+        asset: AssetId,
+        amount: Amount,
+        timestamp: u64,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            (asset as usize) < self.asset_table.len(),
+            "asset id {asset} is not registered"
+        );
+        self.node_count = self.node_count.max(src.max(dst) as usize + 1);
+        self.edge_count += 1;
+        self.out_runs.push(OutRecord {
+            src,
+            dst,
+            amount,
+            timestamp,
+            asset,
+            pad: [0; 14],
+        })
+    }
+
+    /// Merges the runs into a snapshot at `path` with the external ids of `registry`, growing
+    /// the node space to the size of the registry. Like `save_snapshot`, it replaces an existing
+    /// snapshot at `path` by renaming, so processes mapping it keep reading the old one.
+    pub fn finish(mut self, path: impl AsRef<Path>, registry: &NodeRegistry) -> anyhow::Result<()> {
+        self.reserve_nodes(registry.len());
+        let columns = ColumnFiles {
+            srcs_out: self.dir.join("srcs_out.col"),
+            amounts_out: self.dir.join("amounts_out.col"),
+            dsts: self.dir.join("dsts.col"),
+            timestamps_out: self.dir.join("timestamps_out.col"),
+            amounts_in: self.dir.join("amounts_in.col"),
+            srcs_in: self.dir.join("srcs_in.col"),
+            timestamps_in: self.dir.join("timestamps_in.col"),
+            edge_ids_in: self.dir.join("edge_ids_in.col"),
+            assets: self.dir.join("assets.col"),
+        };
+        let _scratch = ScratchFiles(vec![
+            columns.srcs_out.clone(),
+            columns.amounts_out.clone(),
+            columns.dsts.clone(),
+            columns.timestamps_out.clone(),
+            columns.amounts_in.clone(),
+            columns.srcs_in.clone(),
+            columns.timestamps_in.clone(),
+            columns.edge_ids_in.clone(),
+            columns.assets.clone(),
+        ]);
+        self.write(path.as_ref(), registry, &columns)
+    }
+
+    fn write(
+        self,
+        path: &Path,
+        registry: &NodeRegistry,
+        columns: &ColumnFiles,
+    ) -> anyhow::Result<()> {
+        let cfg = ExternalConfig {
+            run_edges: self.out_runs.run_edges,
+            merge_fan_in: self.out_runs.merge_fan_in,
+            sort_by_time: self.sort_by_time,
+        };
+        let mut in_runs = RunSorter::<InRecord>::new(&self.dir, "in", &cfg);
+        let mut counts_out = vec![0; self.node_count];
+        let mut counts_in = vec![0; self.node_count];
+        let mut first_asset = None;
+        let mut mixed = false;
+
+        // out edges in their final order, edge ids are the positions in it
+        let mut srcs_out = ColumnWriter::create(&columns.srcs_out)?;
+        let mut amounts_out = ColumnWriter::create(&columns.amounts_out)?;
+        let mut dsts = ColumnWriter::create(&columns.dsts)?;
+        let mut timestamps_out = ColumnWriter::create(&columns.timestamps_out)?;
+        let mut assets = ColumnWriter::create(&columns.assets)?;
+        let mut edge_id: EdgeId = 0;
+        self.out_runs.merge(|r| {
+            counts_out[r.src as usize] += 1;
+            counts_in[r.dst as usize] += 1;
+            mixed |= *first_asset.get_or_insert(r.asset) != r.asset;
+            srcs_out.write(r.src)?;
+            amounts_out.write(r.amount)?;
+            dsts.write(r.dst)?;
+            timestamps_out.write(r.timestamp)?;
+            assets.write(r.asset)?;
+            in_runs.push(InRecord {
+                dst: r.dst,
+                src: r.src,
+                amount: r.amount,
+                timestamp: r.timestamp,
+                edge_id,
+                pad: [0; 8],
+            })?;
+            edge_id += 1;
+            Ok(())
+        })?;
+        for column in [srcs_out, amounts_out, dsts, timestamps_out, assets] {
+            column.finish()?;
+        }
+
+        let mut amounts_in = ColumnWriter::create(&columns.amounts_in)?;
+        let mut srcs_in = ColumnWriter::create(&columns.srcs_in)?;
+        let mut timestamps_in = ColumnWriter::create(&columns.timestamps_in)?;
+        let mut edge_ids_in = ColumnWriter::create(&columns.edge_ids_in)?;
+        in_runs.merge(|r| {
+            amounts_in.write(r.amount)?;
+            srcs_in.write(r.src)?;
+            timestamps_in.write(r.timestamp)?;
+            edge_ids_in.write(r.edge_id)
+        })?;
+        for column in [amounts_in, srcs_in, timestamps_in, edge_ids_in] {
+            column.finish()?;
+        }
+
+        let asset_scope = match first_asset {
+            Some(_) if mixed => AssetScope::Mixed,
+            first => AssetScope::Single(first.unwrap_or(DEFAULT_ASSET)),
+        };
+        let graph = SpilledGraph {
+            node_count: self.node_count,
+            edge_count: self.edge_count,
+            time_sorted: self.sort_by_time,
+            offsets_out: &offsets(&counts_out),
+            offsets_in: &offsets(&counts_in),
+            columns,
+            asset_names: &self.asset_table.names,
+            asset_decimals: &self.asset_table.decimals,
+            asset_scaled: &self.asset_table.scaled,
+            asset_scope,
+            time_unit: self.time_unit,
+        };
+        snapshot::replace_file(path, |writer| {
+            snapshot::write_spilled_snapshot(writer, &graph, registry)
+        })
+    }
+}
+
+fn offsets(counts: &[usize]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(counts.len() + 1);
+    offsets.push(0);
+    let mut next = 0;
+    for count in counts {
+        next += count;
+        offsets.push(next);
+    }
+    offsets
+}
+
+// Scratch files, removed when dropped so that an error or a panic does not leave them behind.
+#[derive(Default)]
+struct ScratchFiles(Vec<PathBuf>);
+
+impl Drop for ScratchFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            // best effort, a file that was never created is not an error either
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+trait Record: Pod {
+    fn node(&self) -> NodeId;
+    fn timestamp(&self) -> u64;
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct OutRecord {
+    amount: Amount,
+    src: NodeId,
+    dst: NodeId,
+    timestamp: u64,
+    asset: AssetId,
+    pad: [u8; 14],
+}
+
+impl Record for OutRecord {
+    fn node(&self) -> NodeId {
+        self.src
+    }
+
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct InRecord {
+    amount: Amount,
+    timestamp: u64,
+    edge_id: EdgeId,
+    dst: NodeId,
+    src: NodeId,
+    pad: [u8; 8],
+}
+
+impl Record for InRecord {
+    fn node(&self) -> NodeId {
+        self.dst
+    }
+
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+// External merge sort of records by node, and by timestamp within a node when sorting by time.
+// Runs are sorted stably and ties in a merge go to the earlier run, so records with equal keys
+// keep their insertion order like in the in-memory freeze. Passes over more runs than the fan-in
+// merge consecutive groups of runs, which keeps them in order.
+struct RunSorter<R> {
+    dir: PathBuf,
+    prefix: &'static str,
+    run_edges: usize,
+    merge_fan_in: usize,
+    sort_by_time: bool,
+    buffer: Vec<R>,
+    runs: ScratchFiles,
+    next_run: usize,
+}
+
+impl<R: Record> RunSorter<R> {
+    fn new(dir: &Path, prefix: &'static str, cfg: &ExternalConfig) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            prefix,
+            run_edges: cfg.run_edges.max(1),
+            merge_fan_in: cfg.merge_fan_in.max(2),
+            sort_by_time: cfg.sort_by_time,
+            buffer: vec![],
+            runs: ScratchFiles::default(),
+            next_run: 0,
+        }
+    }
+
+    fn run_path(&mut self) -> PathBuf {
+        self.next_run += 1;
+        self.dir
+            .join(format!("{}-{}.run", self.prefix, self.next_run - 1))
+    }
+
+    fn key(&self, record: &R) -> (NodeId, u64) {
+        let timestamp = if self.sort_by_time {
+            record.timestamp()
+        } else {
+            0
+        };
+        (record.node(), timestamp)
+    }
+
+    fn push(&mut self, record: R) -> anyhow::Result<()> {
+        self.buffer.push(record);
+        if self.buffer.len() >= self.run_edges {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> anyhow::Result<()> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.sort_by_key(|r| self.key(r));
+        let path = self.run_path();
+        self.runs.0.push(path.clone());
+        let mut file = BufWriter::new(File::create(&path)?);
+        file.write_all(bytemuck::cast_slice(&buffer))?;
+        file.flush()?;
+        buffer.clear();
+        self.buffer = buffer;
+        Ok(())
+    }
+
+    // Calls `f` with all records in sorted order and removes the runs.
+    fn merge<F>(mut self, f: F) -> anyhow::Result<()>
+    where
+        F: FnMut(R) -> anyhow::Result<()>,
+    {
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        self.buffer = vec![];
+        while self.runs.0.len() > self.merge_fan_in {
+            let runs = std::mem::take(&mut self.runs);
+            for group in runs.0.chunks(self.merge_fan_in) {
+                let path = self.run_path();
+                self.runs.0.push(path.clone());
+                let mut file = BufWriter::new(File::create(&path)?);
+                self.merge_runs(group, |r| Ok(file.write_all(bytemuck::bytes_of(&r))?))?;
+                file.flush()?;
+            }
+        }
+        self.merge_runs(&self.runs.0, f)
+    }
+
+    fn merge_runs<F>(&self, runs: &[PathBuf], mut f: F) -> anyhow::Result<()>
+    where
+        F: FnMut(R) -> anyhow::Result<()>,
+    {
+        let mut readers = runs
+            .iter()
+            .map(|run| Ok(BufReader::new(File::open(run)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut heads = Vec::with_capacity(readers.len());
+        let mut heap = BinaryHeap::with_capacity(readers.len());
+        for (i, reader) in readers.iter_mut().enumerate() {
+            let head = read_record::<R>(reader)?;
+            if let Some(record) = &head {
+                heap.push(Reverse((self.key(record), i)));
+            }
+            heads.push(head);
+        }
+        while let Some(Reverse((_, i))) = heap.pop() {
+            let record = heads[i].take().expect("merge head is missing");
+            heads[i] = read_record(&mut readers[i])?;
+            if let Some(next) = &heads[i] {
+                heap.push(Reverse((self.key(next), i)));
+            }
+            f(record)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_record<R: Pod>(reader: &mut impl Read) -> anyhow::Result<Option<R>> {
+    let mut record = R::zeroed();
+    match reader.read_exact(bytemuck::bytes_of_mut(&mut record)) {
+        Ok(()) => Ok(Some(record)),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+struct ColumnWriter(BufWriter<File>);
+
+impl ColumnWriter {
+    fn create(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self(BufWriter::new(File::create(path)?)))
+    }
+
+    fn write<T: Pod>(&mut self, value: T) -> anyhow::Result<()> {
+        self.0.write_all(bytemuck::bytes_of(&value))?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<()> {
+        self.0.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::graph::{FreezeConfig, GraphBuilder};
+    use crate::core::snapshot::{load_snapshot, map_snapshot, save_snapshot};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_matches_in_memory_freeze() {
+        let dir = std::env::temp_dir().join(format!("traceloc-external-{}", std::process::id()));
+        let mut rng = StdRng::seed_from_u64(7);
+        let edges = (0..500)
+            .map(|_| {
+                (
+                    rng.random_range(0..40),
+                    rng.random_range(0..40),
+                    rng.random_range(0..3),
+                    rng.random_range(0..1000),
+                    rng.random_range(0..50),
+                )
+            })
+            .collect::<Vec<_>>();
+        let registry = NodeRegistry::new();
+
+        // 14 runs, merged at once or over several passes
+        let total = edges.iter().map(|e| e.3).sum::<Amount>();
+        let mut mappings = vec![];
+        for (sort_by_time, merge_fan_in) in [(false, 64), (true, 64), (false, 2), (true, 3)] {
+            let mut gb = GraphBuilder::growable();
+            let mut eb = ExternalBuilder::new(
+                &dir,
+                &ExternalConfig {
+                    run_edges: 37,
+                    merge_fan_in,
+                    sort_by_time,
+                },
+            )
+            .unwrap();
+            for name in ["", "eth", "usdc"] {
+                assert_eq!(gb.asset_id(name).unwrap(), eb.asset_id(name).unwrap());
+            }
+            gb.set_asset_decimals(1, 18).unwrap();
+            eb.set_asset_decimals(1, 18).unwrap();
+            for (src, dst, asset, amount, ts) in &edges {
+                gb.add_asset_edge(*src, *dst, *asset, *amount, *ts, &[])
+                    .unwrap();
+                eb.add_asset_edge(*src, *dst, *asset, *amount, *ts).unwrap();
+            }
+            gb.reserve_nodes(45);
+            eb.reserve_nodes(45);
+
+            let expected_path = dir.join("expected.snap");
+            let path = dir.join("external.snap");
+            let g = gb.freeze_with(&FreezeConfig {
+                sort_by_time,
+                ..Default::default()
+            });
+            save_snapshot(&expected_path, &g, &registry).unwrap();
+            eb.finish(&path, &registry).unwrap();
+            assert_eq!(fs::read(&expected_path).unwrap(), fs::read(&path).unwrap());
+
+            let (mapped, _) = map_snapshot(&path).unwrap();
+            let (loaded, _) = load_snapshot(&path).unwrap();
+            assert_eq!(45, mapped.node_count());
+            assert_eq!(500, loaded.edge_count());
+            mappings.push(mapped);
+        }
+        // the snapshot is replaced on each pass, the earlier mappings still read their own
+        for mapped in &mappings {
+            let sum = (0..45).flat_map(|n| mapped.edges_from(n)).map(|e| e.amount);
+            assert_eq!(total, sum.sum::<Amount>());
+        }
+        let mut left = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect::<Vec<_>>();
+        left.sort();
+        assert_eq!(vec!["expected.snap", "external.snap"], left);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dropped_builder_removes_runs() {
+        let dir = std::env::temp_dir().join(format!("traceloc-dropped-{}", std::process::id()));
+        let mut eb = ExternalBuilder::new(
+            &dir,
+            &ExternalConfig {
+                run_edges: 10,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        for i in 0..100 {
+            eb.add_edge(i % 7, i % 5, 1, i as u64).unwrap();
+        }
+        assert_eq!(10, fs::read_dir(&dir).unwrap().count());
+        drop(eb);
+        assert_eq!(0, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,119 +1,572 @@
-use crate::core::ids::NodeId;
+use crate::core::adjacency::{Adjacency, AdjacencyIter};
+use crate::core::aggregate::{self, Aggregates};
+use crate::core::amount::Amount;
+use crate::core::assets::{AssetScope, AssetTable};
+use crate::core::attributes::{
+    AttributeBuilder, AttributeColumn, AttributeId, AttributeKind, AttributeValue,
+};
+use crate::core::column::Column;
+use crate::core::ids::{AssetId, DEFAULT_ASSET, EdgeId, NodeId};
+use crate::core::memory::{self, MemoryBudget};
+use crate::core::parallel;
+use crate::core::time::TimeUnit;
+use anyhow::{bail, ensure};
+use std::ops::Range;
+
+#[derive(Clone)]
+pub struct FreezeConfig {
+    /// Order each node's incoming and outgoing edges by timestamp, enabling the binary search
+    /// in `edges_from_between` and `edges_to_between`.
+    pub sort_by_time: bool,
+    /// Worker threads used by freeze; the output does not depend on it. With a single thread
+    /// the edges are sorted in place, more threads need a second copy of the edge arrays.
+    pub threads: usize,
+    /// Store neighbour ids and timestamps as delta encoded varints, decoded on the fly while
+    /// iterating. Time window queries then scan the whole node instead of a binary search.
+    pub compress: bool,
+    /// Collapse transfers with the same source, destination and asset into one edge carrying
+    /// their total amount and first timestamp, see `Graph::aggregate` and `Graph::transfers`.
+    /// Only `GraphBuilder::try_freeze_with` aggregates, as a total can overflow.
+    pub aggregate_parallel: bool,
+}
+
+impl Default for FreezeConfig {
+    fn default() -> Self {
+        Self {
+            sort_by_time: false,
+            threads: 1,
+            compress: false,
+            aggregate_parallel: false,
+        }
+    }
+}
+
+/// Edges added between two checks of a memory budget that the edge columns do not grow in.
+pub const BUDGET_CHECK_EDGES: usize = 1 << 12;
 
 pub struct GraphBuilder {
-    graph: Graph,
+    node_count: usize,
+    growable: bool,
+    pub(crate) srcs: Vec<NodeId>,
+    pub(crate) dsts: Vec<NodeId>,
+    pub(crate) amounts: Vec<Amount>,
+    pub(crate) timestamps: Vec<u64>,
+    pub(crate) assets: Vec<AssetId>,
+    pub(crate) asset_table: AssetTable,
+    pub(crate) time_unit: TimeUnit,
+    pub(crate) attributes: Vec<AttributeBuilder>,
+    memory_budget: Option<MemoryBudget>,
 }
 
 impl GraphBuilder {
+    /// Builder over a fixed node space `0..node_count`; edges with other ids are rejected.
     pub fn new(node_count: usize) -> Self {
         Self {
-            graph: Graph::new(node_count),
+            node_count,
+            growable: false,
+            srcs: vec![],
+            dsts: vec![],
+            amounts: vec![],
+            timestamps: vec![],
+            assets: vec![],
+            asset_table: AssetTable::default(),
+            time_unit: TimeUnit::Seconds,
+            attributes: vec![],
+            memory_budget: None,
+        }
+    }
+
+    /// Builder whose node space grows to cover every node id added, for ingest where the
+    /// number of nodes is only known at the end.
+    pub fn growable() -> Self {
+        Self {
+            growable: true,
+            ..Self::new(0)
         }
     }
 
-    pub fn add_edge(&mut self, src: NodeId, dst: NodeId, amount: u64, timestamp: u64) {
-        self.graph.srcs_out.push(src);
-        self.graph.dsts.push(dst);
-        self.graph.amounts_out.push(amount);
-        self.graph.timestamps_out.push(timestamp);
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    /// Grows the node space to at least `node_count` nodes, e.g. to the size of the
+    /// `NodeRegistry` before freeze so that nodes without edges are included.
+    pub fn reserve_nodes(&mut self, node_count: usize) {
+        self.node_count = self.node_count.max(node_count);
+    }
+
+    pub fn reserve_edges(&mut self, additional: usize) {
+        self.srcs.reserve_exact(additional);
+        self.dsts.reserve_exact(additional);
+        self.amounts.reserve_exact(additional);
+        self.timestamps.reserve_exact(additional);
+        self.assets.reserve_exact(additional);
+    }
+
+    /// Makes adding an edge fail once the predicted peak memory of building and freezing the
+    /// graph would exceed the budget. The budget is checked whenever the edge columns grow and
+    /// every `BUDGET_CHECK_EDGES` edges in between.
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.memory_budget = budget;
+    }
+
+    /// Checks that one more edge fits into the memory budget together with `other_bytes` used
+    /// elsewhere, e.g. by the `NodeRegistry` filled during ingest.
+    pub fn check_memory_budget(&self, other_bytes: usize) -> anyhow::Result<()> {
+        self.check_budget(self.node_count, other_bytes)
+    }
+
+    // Whether the next edge is due a budget check: when it grows the edge columns, which is
+    // when the predicted peak jumps, and periodically for new nodes and attribute strings.
+    pub(crate) fn budget_check_due(&self) -> bool {
+        self.memory_budget.is_some()
+            && (self.srcs.len() == self.srcs.capacity()
+                || (self.srcs.len() + 1).is_multiple_of(BUDGET_CHECK_EDGES))
+    }
+
+    fn check_budget(&self, node_count: usize, other_bytes: usize) -> anyhow::Result<()> {
+        let Some(budget) = &self.memory_budget else {
+            return Ok(());
+        };
+        let edge_count = self.srcs.len() + 1;
+        let capacity = match self.srcs.capacity() {
+            cap if cap >= edge_count => cap,
+            cap => (2 * cap).max(edge_count),
+        };
+        let needed = memory::predict_freeze_peak(node_count, capacity, &budget.freeze)
+            + 2 * self
+                .attributes
+                .iter()
+                .map(|a| a.heap_bytes())
+                .sum::<usize>()
+            + other_bytes;
+        budget.check(needed, edge_count)
+    }
+
+    /// Id of the asset `name`, registering it if it is new; fails once all `AssetId`s are
+    /// taken.
+    pub fn asset_id(&mut self, name: &str) -> anyhow::Result<AssetId> {
+        self.asset_table.id(name)
     }
 
-    pub fn freeze(mut self) -> Graph {
-        if self.graph.edge_count() == 0 {
-            return self.graph;
+    /// Sets the number of decimal places of one unit of `asset`, i.e. amounts of it are in
+    /// units of `10^-decimals`; assets have no decimals until set.
+    pub fn set_asset_decimals(&mut self, asset: AssetId, decimals: u8) -> anyhow::Result<()> {
+        self.asset_table.set_decimals(asset, decimals)
+    }
+
+    /// Sets the unit of the edge timestamps, seconds until set.
+    pub fn set_time_unit(&mut self, unit: TimeUnit) {
+        self.time_unit = unit;
+    }
+
+    /// Registers a per-edge attribute column. Edges added before the registration, or through
+    /// `add_edge`, get a default value (`0` or an empty string).
+    pub fn add_attribute(
+        &mut self,
+        name: &str,
+        kind: AttributeKind,
+    ) -> anyhow::Result<AttributeId> {
+        if self.attributes.iter().any(|a| a.name == name) {
+            bail!("attribute {name} is already registered");
         }
+        self.attributes
+            .push(AttributeBuilder::new(name, kind, self.srcs.len()));
+        Ok(self.attributes.len() - 1)
+    }
+
+    /// Adds an edge without attribute values; a node id out of range of a fixed-size builder is
+    /// an error.
+    pub fn add_edge(
+        &mut self,
+        src: NodeId,
+        dst: NodeId,
+        amount: Amount,
+        timestamp: u64,
+    ) -> anyhow::Result<()> {
+        self.node_count = self.check_nodes(src, dst)?;
+        self.srcs.push(src);
+        self.dsts.push(dst);
+        self.amounts.push(amount);
+        self.timestamps.push(timestamp);
+        self.assets.push(DEFAULT_ASSET);
+        for attr in &mut self.attributes {
+            attr.push_default();
+        }
+        Ok(())
+    }
 
-        let mut buf = vec![0; self.graph.node_count];
+    /// Adds an edge with one value per registered attribute, in registration order.
+    pub fn add_edge_with_attributes(
+        &mut self,
+        src: NodeId,
+        dst: NodeId,
+        amount: Amount,
+        timestamp: u64,
+        attributes: &[AttributeValue],
+    ) -> anyhow::Result<()> {
+        self.add_asset_edge(src, dst, DEFAULT_ASSET, amount, timestamp, attributes)
+    }
+
+    /// Adds an edge transferring `amount` base units of an asset obtained from `asset_id`.
+    pub fn add_asset_edge(
+        &mut self,
+        src: NodeId,
+        dst: NodeId,
+        asset: AssetId,
+        amount: Amount,
+        timestamp: u64,
+        attributes: &[AttributeValue],
+    ) -> anyhow::Result<()> {
+        ensure!(
+            (asset as usize) < self.asset_table.len(),
+            "unknown asset {asset}"
+        );
+        self.check_attributes(attributes)?;
+        self.node_count = self.check_nodes(src, dst)?;
 
-        // store number of edges per dst node
-        for dst in &self.graph.dsts {
-            buf[*dst as usize] += 1;
+        self.srcs.push(src);
+        self.dsts.push(dst);
+        self.amounts.push(amount);
+        self.timestamps.push(timestamp);
+        self.assets.push(asset);
+        for (attr, value) in self.attributes.iter_mut().zip(attributes) {
+            attr.push(value);
         }
+        Ok(())
+    }
 
-        // compute edge offsets per dst node
-        let mut next = 0;
-        for (i, edges) in buf.iter().enumerate() {
-            let from = next;
-            let to = from + edges;
-            self.graph.offsets_in[i] = from;
-            self.graph.offsets_in[i + 1] = to;
-            next = to;
+    pub(crate) fn check_attributes(&self, attributes: &[AttributeValue]) -> anyhow::Result<()> {
+        ensure!(
+            attributes.len() == self.attributes.len(),
+            "expected {} attribute values, got {}",
+            self.attributes.len(),
+            attributes.len()
+        );
+        for (attr, value) in self.attributes.iter().zip(attributes) {
+            attr.check(value)?;
         }
+        Ok(())
+    }
 
-        buf.fill(0);
-        self.graph.srcs_in = vec![0; self.graph.edge_count()];
-        self.graph.timestamps_in = vec![0; self.graph.edge_count()];
-        for e in 0..self.graph.edge_count() {
-            let dst = self.graph.dsts[e] as usize;
-            let idx = self.graph.offsets_in[dst] + buf[dst];
-            self.graph.srcs_in[idx] = self.graph.srcs_out[e];
-            self.graph.timestamps_in[idx] = self.graph.timestamps_out[e];
-            buf[dst] += 1;
+    // Checks the node ids of an edge and the memory budget with it, without adding it. Returns
+    // the node count with the edge, which only differs for a growable builder.
+    pub(crate) fn check_nodes(&self, src: NodeId, dst: NodeId) -> anyhow::Result<usize> {
+        let Some(node_count) = self.node_count_with(src, dst) else {
+            bail!(
+                "node id {} is out of range for a graph of {} nodes",
+                src.max(dst),
+                self.node_count
+            );
+        };
+        if self.budget_check_due() {
+            self.check_budget(node_count, 0)?;
         }
+        Ok(node_count)
+    }
 
-        buf.fill(0);
-        // store number of edges per src node
-        for src in &self.graph.srcs_out {
-            buf[*src as usize] += 1;
+    // Node count with an edge between `src` and `dst`, `None` if they do not fit.
+    pub(crate) fn node_count_with(&self, src: NodeId, dst: NodeId) -> Option<usize> {
+        let max = src.max(dst) as usize;
+        match self.growable {
+            true => Some(self.node_count.max(max + 1)),
+            false => (max < self.node_count).then_some(self.node_count),
         }
+    }
+
+    pub fn freeze(self) -> Graph {
+        self.freeze_with(&FreezeConfig::default())
+    }
 
-        // compute edge offsets per source node
-        next = 0;
-        for (i, edges) in buf.iter().enumerate() {
-            let from = next;
-            let to = from + edges;
-            self.graph.offsets_out[i] = from;
-            self.graph.offsets_out[i + 1] = to;
-            next = to;
+    /// # Panics
+    ///
+    /// With `FreezeConfig::aggregate_parallel`, as the total amount of an aggregated edge can
+    /// overflow; aggregate with `try_freeze_with`.
+    pub fn freeze_with(self, cfg: &FreezeConfig) -> Graph {
+        assert!(
+            !cfg.aggregate_parallel,
+            "aggregate_parallel can fail, freeze with try_freeze_with"
+        );
+        self.freeze_edges(cfg)
+    }
+
+    /// Like `freeze_with`, and also aggregates parallel edges. An aggregated total amount that
+    /// overflows is an error.
+    pub fn try_freeze_with(mut self, cfg: &FreezeConfig) -> anyhow::Result<Graph> {
+        let aggregates = match cfg.aggregate_parallel {
+            true => Some(aggregate::collapse(&mut self, cfg)?),
+            false => None,
+        };
+        let mut graph = self.freeze_edges(cfg);
+        graph.aggregates = aggregates;
+        Ok(graph)
+    }
+
+    fn freeze_edges(self, cfg: &FreezeConfig) -> Graph {
+        if cfg.threads > 1 {
+            self.freeze_parallel(cfg)
+        } else {
+            self.freeze_sequential(cfg)
         }
+    }
+
+    fn freeze_sequential(mut self, cfg: &FreezeConfig) -> Graph {
+        let edge_count = self.srcs.len();
+        let mut offsets_out = vec![0; self.node_count + 1];
+        let mut offsets_in = vec![0; self.node_count + 1];
+        let mut srcs_in = vec![0; edge_count];
+        let mut amounts_in = vec![0; edge_count];
+        let mut timestamps_in = vec![0; edge_count];
+        let mut edge_ids_in = vec![0; edge_count];
+
+        if edge_count > 0 {
+            let mut buf = vec![0; self.node_count];
 
-        buf.fill(0);
-        let mut e = 0;
-        for _ in 0..self.graph.edge_count() {
-            let src = self.graph.srcs_out[e] as usize;
-            let idx = self.graph.offsets_out[src] + buf[src];
-            if idx != e {
-                self.graph.srcs_out.swap(idx, e);
-                self.graph.dsts.swap(idx, e);
-                self.graph.amounts_out.swap(idx, e);
-                self.graph.timestamps_out.swap(idx, e);
-            } else {
-                e += 1;
+            // store number of edges per src node
+            for src in &self.srcs {
+                buf[*src as usize] += 1;
+            }
+
+            // compute edge offsets per source node
+            let mut next = 0;
+            for (i, edges) in buf.iter().enumerate() {
+                let from = next;
+                let to = from + edges;
+                offsets_out[i] = from;
+                offsets_out[i + 1] = to;
+                next = to;
+            }
+
+            // compute target position per edge, keeping insertion order within a source node
+            buf.fill(0);
+            let mut targets = Vec::with_capacity(edge_count);
+            for src in &self.srcs {
+                let src = *src as usize;
+                targets.push(offsets_out[src] + buf[src]);
+                buf[src] += 1;
+            }
+
+            // move edges in place by following the permutation cycles
+            for e in 0..edge_count {
+                while targets[e] != e {
+                    let idx = targets[e];
+                    self.srcs.swap(idx, e);
+                    self.dsts.swap(idx, e);
+                    self.amounts.swap(idx, e);
+                    self.timestamps.swap(idx, e);
+                    self.assets.swap(idx, e);
+                    for attr in &mut self.attributes {
+                        attr.values.swap(idx, e);
+                    }
+                    targets.swap(idx, e);
+                }
+            }
+
+            if cfg.sort_by_time {
+                for src in 0..self.node_count {
+                    let range = offsets_out[src]..offsets_out[src + 1];
+                    if let Some(perm) = time_order(&self.timestamps[range.clone()]) {
+                        permute(&mut self.dsts[range.clone()], &perm);
+                        permute(&mut self.amounts[range.clone()], &perm);
+                        permute(&mut self.timestamps[range.clone()], &perm);
+                        permute(&mut self.assets[range.clone()], &perm);
+                        for attr in &mut self.attributes {
+                            permute(&mut attr.values[range.clone()], &perm);
+                        }
+                    }
+                }
+            }
+
+            buf.fill(0);
+            // store number of edges per dst node
+            for dst in &self.dsts {
+                buf[*dst as usize] += 1;
+            }
+
+            // compute edge offsets per dst node
+            next = 0;
+            for (i, edges) in buf.iter().enumerate() {
+                let from = next;
+                let to = from + edges;
+                offsets_in[i] = from;
+                offsets_in[i + 1] = to;
+                next = to;
+            }
+
+            // edges are scattered in out-CSR order, so the edge id is the out-CSR position
+            buf.fill(0);
+            for e in 0..edge_count {
+                let dst = self.dsts[e] as usize;
+                let idx = offsets_in[dst] + buf[dst];
+                srcs_in[idx] = self.srcs[e];
+                amounts_in[idx] = self.amounts[e];
+                timestamps_in[idx] = self.timestamps[e];
+                edge_ids_in[idx] = e as EdgeId;
+                buf[dst] += 1;
+            }
+
+            if cfg.sort_by_time {
+                for dst in 0..self.node_count {
+                    let range = offsets_in[dst]..offsets_in[dst + 1];
+                    if let Some(perm) = time_order(&timestamps_in[range.clone()]) {
+                        permute(&mut srcs_in[range.clone()], &perm);
+                        permute(&mut amounts_in[range.clone()], &perm);
+                        permute(&mut timestamps_in[range.clone()], &perm);
+                        permute(&mut edge_ids_in[range], &perm);
+                    }
+                }
             }
-            buf[src] += 1;
         }
 
-        self.graph
+        self.into_graph(
+            cfg,
+            offsets_out,
+            InCsr {
+                offsets: offsets_in,
+                srcs: srcs_in,
+                amounts: amounts_in,
+                timestamps: timestamps_in,
+                edge_ids: edge_ids_in,
+            },
+        )
+    }
+
+    // Same CSR layout as the single-threaded path, built out of place: edges are bucketed into
+    // an order of output positions, which is then used to gather every column.
+    fn freeze_parallel(mut self, cfg: &FreezeConfig) -> Graph {
+        let threads = cfg.threads;
+
+        let (offsets_out, mut order) = parallel::bucket_order(&self.srcs, self.node_count, threads);
+        if cfg.sort_by_time {
+            let timestamps = &self.timestamps;
+            parallel::sort_buckets_by_key(&mut order, &offsets_out, |e| timestamps[e], threads);
+        }
+        self.srcs = parallel::gather(&self.srcs, &order, threads);
+        self.dsts = parallel::gather(&self.dsts, &order, threads);
+        self.amounts = parallel::gather(&self.amounts, &order, threads);
+        self.timestamps = parallel::gather(&self.timestamps, &order, threads);
+        self.assets = parallel::gather(&self.assets, &order, threads);
+        for attr in &mut self.attributes {
+            attr.values = parallel::gather(&attr.values, &order, threads);
+        }
+        drop(order);
+
+        let (offsets_in, mut order) = parallel::bucket_order(&self.dsts, self.node_count, threads);
+        if cfg.sort_by_time {
+            let timestamps = &self.timestamps;
+            parallel::sort_buckets_by_key(&mut order, &offsets_in, |e| timestamps[e], threads);
+        }
+        let in_csr = InCsr {
+            offsets: offsets_in,
+            srcs: parallel::gather(&self.srcs, &order, threads),
+            amounts: parallel::gather(&self.amounts, &order, threads),
+            timestamps: parallel::gather(&self.timestamps, &order, threads),
+            edge_ids: order.into_iter().map(|e| e as EdgeId).collect(),
+        };
+
+        self.into_graph(cfg, offsets_out, in_csr)
+    }
+
+    fn into_graph(mut self, cfg: &FreezeConfig, offsets_out: Vec<usize>, in_csr: InCsr) -> Graph {
+        // the builder grew its columns by doubling, the frozen graph keeps only what it needs
+        self.srcs.shrink_to_fit();
+        self.dsts.shrink_to_fit();
+        self.amounts.shrink_to_fit();
+        self.timestamps.shrink_to_fit();
+        self.assets.shrink_to_fit();
+        let asset_scope = match self.assets.first() {
+            None => AssetScope::Single(DEFAULT_ASSET),
+            Some(first) if self.assets.iter().all(|a| a == first) => AssetScope::Single(*first),
+            Some(_) => AssetScope::Mixed,
+        };
+
+        let (adj_out, adj_in) = if cfg.compress {
+            (
+                Adjacency::compress(&self.dsts, &self.timestamps, &offsets_out),
+                Adjacency::compress(&in_csr.srcs, &in_csr.timestamps, &in_csr.offsets),
+            )
+        } else {
+            (
+                Adjacency::Plain {
+                    nodes: self.dsts.into(),
+                    timestamps: self.timestamps.into(),
+                },
+                Adjacency::Plain {
+                    nodes: in_csr.srcs.into(),
+                    timestamps: in_csr.timestamps.into(),
+                },
+            )
+        };
+
+        Graph {
+            node_count: self.node_count,
+            time_sorted: cfg.sort_by_time,
+            srcs_out: self.srcs.into(),
+            adj_out,
+            adj_in,
+            amounts_out: self.amounts.into(),
+            amounts_in: in_csr.amounts.into(),
+            edge_ids_in: in_csr.edge_ids.into(),
+            assets: self.assets.into(),
+            asset_names: self.asset_table.names,
+            asset_decimals: self.asset_table.decimals,
+            asset_scaled: self.asset_table.scaled,
+            asset_scope,
+            time_unit: self.time_unit,
+            offsets_out: offsets_out.into(),
+            offsets_in: in_csr.offsets.into(),
+            attributes: self.attributes.into_iter().map(|a| a.freeze()).collect(),
+            aggregates: None,
+        }
     }
 }
 
-pub struct Graph {
-    node_count: usize,
-    srcs_out: Vec<NodeId>,
-    srcs_in: Vec<NodeId>,
-    dsts: Vec<NodeId>,
-    amounts_out: Vec<u64>,
-    timestamps_in: Vec<u64>,
-    timestamps_out: Vec<u64>,
-    offsets_out: Vec<usize>,
-    offsets_in: Vec<usize>,
+struct InCsr {
+    offsets: Vec<usize>,
+    srcs: Vec<NodeId>,
+    amounts: Vec<Amount>,
+    timestamps: Vec<u64>,
+    edge_ids: Vec<EdgeId>,
 }
 
-impl Graph {
-    fn new(node_count: usize) -> Self {
-        Self {
-            node_count,
-            srcs_out: vec![],
-            srcs_in: vec![],
-            dsts: vec![],
-            amounts_out: vec![],
-            timestamps_in: vec![],
-            timestamps_out: vec![],
-            offsets_out: vec![0; node_count + 1],
-            offsets_in: vec![0; node_count + 1],
-        }
+// Stable ordering of the edges by timestamp, `None` if they are already sorted.
+fn time_order(timestamps: &[u64]) -> Option<Vec<usize>> {
+    if timestamps.is_sorted() {
+        return None;
     }
+    let mut perm = (0..timestamps.len()).collect::<Vec<usize>>();
+    perm.sort_by_key(|i| timestamps[*i]);
+    Some(perm)
+}
 
+fn permute<T: Copy>(values: &mut [T], perm: &[usize]) {
+    let permuted = perm.iter().map(|i| values[*i]).collect::<Vec<T>>();
+    values.copy_from_slice(&permuted);
+}
+
+pub struct Graph {
+    pub(crate) node_count: usize,
+    pub(crate) time_sorted: bool,
+    pub(crate) srcs_out: Column<NodeId>,
+    pub(crate) adj_out: Adjacency,
+    pub(crate) adj_in: Adjacency,
+    pub(crate) amounts_out: Column<Amount>,
+    pub(crate) amounts_in: Column<Amount>,
+    pub(crate) edge_ids_in: Column<EdgeId>,
+    pub(crate) assets: Column<AssetId>,
+    pub(crate) asset_names: Vec<String>,
+    pub(crate) asset_decimals: Vec<u8>,
+    // whether the decimals of an asset were set, see `AssetTable`
+    pub(crate) asset_scaled: Vec<bool>,
+    pub(crate) asset_scope: AssetScope,
+    pub(crate) time_unit: TimeUnit,
+    pub(crate) offsets_out: Column<usize>,
+    pub(crate) offsets_in: Column<usize>,
+    pub(crate) attributes: Vec<AttributeColumn>,
+    pub(crate) aggregates: Option<Aggregates>,
+}
+
+impl Graph {
     pub fn edge_count(&self) -> usize {
         self.srcs_out.len()
     }
@@ -137,21 +590,193 @@ impl Graph {
     pub fn out_degree(&self, src: NodeId) -> usize {
         self.offsets_out[src as usize + 1] - self.offsets_out[src as usize]
     }
+
+    /// Outgoing edges of `src` with a timestamp in `t0..=t1`.
+    pub fn edges_from_between(
+        &'_ self,
+        src: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        let range = self.time_window(&self.offsets_out, &self.adj_out, src, t0, t1);
+        OutgoingEdgeIter::from_range(self, src, range)
+            .filter(move |e| (t0..=t1).contains(&e.timestamp))
+    }
+
+    /// Incoming edges of `dst` with a timestamp in `t0..=t1`.
+    pub fn edges_to_between(
+        &'_ self,
+        dst: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        let range = self.time_window(&self.offsets_in, &self.adj_in, dst, t0, t1);
+        IncomingEdgeIter::from_range(self, dst, range)
+            .filter(move |e| (t0..=t1).contains(&e.timestamp))
+    }
+
+    // Narrows the node's edge range with a binary search when the adjacency is time sorted and
+    // not compressed, otherwise returns the whole range.
+    fn time_window(
+        &self,
+        offsets: &[usize],
+        adj: &Adjacency,
+        node_id: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> Range<usize> {
+        let start = offsets[node_id as usize];
+        let end = offsets[node_id as usize + 1];
+        if t0 > t1 {
+            return start..start;
+        }
+        let Some(timestamps) = adj.timestamps().filter(|_| self.time_sorted) else {
+            return start..end;
+        };
+        let ts = &timestamps[start..end];
+        start + ts.partition_point(|t| *t < t0)..start + ts.partition_point(|t| *t <= t1)
+    }
+
+    pub fn edge_asset(&self, edge: EdgeId) -> AssetId {
+        self.assets[edge as usize]
+    }
+
+    pub fn asset_id(&self, name: &str) -> Option<AssetId> {
+        self.asset_names
+            .iter()
+            .position(|a| a == name)
+            .map(|a| a as AssetId)
+    }
+
+    pub fn asset_name(&self, asset: AssetId) -> Option<&str> {
+        self.asset_names.get(asset as usize).map(|a| a.as_str())
+    }
+
+    /// Decimal places of one unit of `asset`, see `GraphBuilder::set_asset_decimals`.
+    pub fn asset_decimals(&self, asset: AssetId) -> Option<u8> {
+        self.asset_decimals.get(asset as usize).copied()
+    }
+
+    /// Which asset the amounts of this graph are denominated in.
+    pub fn asset_scope(&self) -> AssetScope {
+        self.asset_scope
+    }
+
+    pub fn time_unit(&self) -> TimeUnit {
+        self.time_unit
+    }
+
+    // Copies the graph into a new one with the same asset table, attribute schema, time unit
+    // and time ordering; `f` gets every edge with its source and returns the new endpoints and
+    // amount, or `None` to drop it. Aggregated edges are copied as single edges, without
+    // transfers.
+    pub(crate) fn rebuild(
+        &self,
+        node_count: usize,
+        f: impl FnMut(NodeId, &OutgoingEdgeRef) -> Option<(NodeId, NodeId, Amount)>,
+    ) -> Graph {
+        self.rebuild_from(node_count, 0..self.node_count as NodeId, f)
+    }
+
+    // Like `rebuild`, visiting only the outgoing edges of `sources`.
+    pub(crate) fn rebuild_from(
+        &self,
+        node_count: usize,
+        sources: impl IntoIterator<Item = NodeId>,
+        mut f: impl FnMut(NodeId, &OutgoingEdgeRef) -> Option<(NodeId, NodeId, Amount)>,
+    ) -> Graph {
+        let mut gb = GraphBuilder::new(node_count);
+        gb.asset_table = self.asset_table();
+        gb.time_unit = self.time_unit;
+        for attr in &self.attributes {
+            gb.add_attribute(attr.name(), attr.kind())
+                .expect("attribute names are unique");
+        }
+        let mut values = Vec::with_capacity(self.attributes.len());
+        for src in sources {
+            for e in self.edges_from(src) {
+                if let Some((new_src, new_dst, amount)) = f(src, &e) {
+                    values.clear();
+                    values.extend(self.attributes.iter().map(|a| a.get(e.id as usize)));
+                    gb.add_asset_edge(
+                        new_src,
+                        new_dst,
+                        self.edge_asset(e.id),
+                        amount,
+                        e.timestamp,
+                        &values,
+                    )
+                    .expect("edge matches the copied schema");
+                }
+            }
+        }
+        gb.freeze_with(&FreezeConfig {
+            sort_by_time: self.time_sorted,
+            compress: self.is_compressed(),
+            ..Default::default()
+        })
+    }
+
+    pub fn attribute_id(&self, name: &str) -> Option<AttributeId> {
+        self.attributes.iter().position(|a| a.name() == name)
+    }
+
+    pub fn attributes(&self) -> &[AttributeColumn] {
+        &self.attributes
+    }
+
+    /// Value of an attribute for the edge with the given id, as exposed by the edge refs.
+    pub fn attribute(&self, attr: AttributeId, edge: EdgeId) -> AttributeValue<'_> {
+        self.attributes[attr].get(edge as usize)
+    }
+
+    pub fn is_time_sorted(&self) -> bool {
+        self.time_sorted
+    }
+
+    /// Whether the edge arrays are served from a memory-mapped snapshot.
+    pub fn is_mapped(&self) -> bool {
+        self.amounts_out.is_mapped()
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.adj_out.is_compressed()
+    }
+
+    /// Size of the neighbour ids and timestamps of both directions as plain arrays, divided by
+    /// their actual size; `1.0` for an uncompressed graph. Amounts, edge ids, assets and
+    /// attributes are never compressed and not part of the ratio, see `memory_report` for the
+    /// size of the whole graph.
+    pub fn adjacency_compression_ratio(&self) -> f64 {
+        let plain = 2 * self.edge_count() * (size_of::<NodeId>() + size_of::<u64>());
+        let actual = self.adj_out.size_bytes() + self.adj_in.size_bytes();
+        if actual == 0 || !self.is_compressed() {
+            return 1.0;
+        }
+        plain as f64 / actual as f64
+    }
 }
 
 pub struct IncomingEdgeIter<'a> {
-    graph: &'a Graph,
-    start: usize,
-    end: usize,
+    edges: AdjacencyIter<'a>,
+    amounts: &'a [Amount],
+    edge_ids: &'a [EdgeId],
     next: usize,
 }
 
 impl<'a> IncomingEdgeIter<'a> {
     pub fn new(graph: &'a Graph, node_id: NodeId) -> Self {
+        let start = graph.offsets_in[node_id as usize];
+        let end = graph.offsets_in[node_id as usize + 1];
+        Self::from_range(graph, node_id, start..end)
+    }
+
+    fn from_range(graph: &'a Graph, node_id: NodeId, range: Range<usize>) -> Self {
+        let node_start = graph.offsets_in[node_id as usize];
         Self {
-            graph,
-            start: graph.offsets_in[node_id as usize],
-            end: graph.offsets_in[node_id as usize + 1],
+            edges: graph.adj_in.iter(node_id, node_start, range.clone()),
+            amounts: &graph.amounts_in[range.clone()],
+            edge_ids: &graph.edge_ids_in[range],
             next: 0,
         }
     }
@@ -161,32 +786,44 @@ impl<'a> Iterator for IncomingEdgeIter<'a> {
     type Item = IncomingEdgeRef;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.start + self.next < self.end {
-            let result = Some(IncomingEdgeRef::new(
-                self.graph.srcs_in[self.start + self.next],
-                self.graph.timestamps_in[self.start + self.next],
-            ));
-            self.next += 1;
-            result
-        } else {
-            None
-        }
+        let (src, timestamp) = self.edges.next()?;
+        let result = Some(IncomingEdgeRef::new(
+            self.edge_ids[self.next],
+            src,
+            self.amounts[self.next],
+            timestamp,
+        ));
+        self.next += 1;
+        result
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.edges.len(), Some(self.edges.len()))
     }
 }
 
+impl ExactSizeIterator for IncomingEdgeIter<'_> {}
+
 pub struct OutgoingEdgeIter<'a> {
-    graph: &'a Graph,
     start: usize,
-    end: usize,
+    edges: AdjacencyIter<'a>,
+    amounts: &'a [Amount],
     next: usize,
 }
 
 impl<'a> OutgoingEdgeIter<'a> {
     pub fn new(graph: &'a Graph, node_id: NodeId) -> Self {
+        let start = graph.offsets_out[node_id as usize];
+        let end = graph.offsets_out[node_id as usize + 1];
+        Self::from_range(graph, node_id, start..end)
+    }
+
+    fn from_range(graph: &'a Graph, node_id: NodeId, range: Range<usize>) -> Self {
+        let node_start = graph.offsets_out[node_id as usize];
         Self {
-            graph,
-            start: graph.offsets_out[node_id as usize],
-            end: graph.offsets_out[node_id as usize + 1],
+            start: range.start,
+            edges: graph.adj_out.iter(node_id, node_start, range.clone()),
+            amounts: &graph.amounts_out[range],
             next: 0,
         }
     }
@@ -196,30 +833,36 @@ impl<'a> Iterator for OutgoingEdgeIter<'a> {
     type Item = OutgoingEdgeRef;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.start + self.next < self.end {
-            let result = Some(OutgoingEdgeRef::new(
-                self.graph.dsts[self.start + self.next],
-                self.graph.amounts_out[self.start + self.next],
-                self.graph.timestamps_out[self.start + self.next],
-            ));
-            self.next += 1;
-            result
-        } else {
-            None
-        }
+        let (dst, timestamp) = self.edges.next()?;
+        let result = Some(OutgoingEdgeRef::new(
+            (self.start + self.next) as EdgeId,
+            dst,
+            self.amounts[self.next],
+            timestamp,
+        ));
+        self.next += 1;
+        result
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.edges.len(), Some(self.edges.len()))
     }
 }
 
+impl ExactSizeIterator for OutgoingEdgeIter<'_> {}
+
 #[derive(Debug, PartialEq)]
 pub struct OutgoingEdgeRef {
+    pub id: EdgeId,
     pub dst: NodeId,
-    pub amount: u64,
+    pub amount: Amount,
     pub timestamp: u64,
 }
 
 impl OutgoingEdgeRef {
-    pub fn new(dst: NodeId, amount: u64, timestamp: u64) -> Self {
+    pub fn new(id: EdgeId, dst: NodeId, amount: Amount, timestamp: u64) -> Self {
         Self {
+            id,
             dst,
             amount,
             timestamp,
@@ -229,13 +872,20 @@ impl OutgoingEdgeRef {
 
 #[derive(Debug, PartialEq)]
 pub struct IncomingEdgeRef {
+    pub id: EdgeId,
     pub src: NodeId,
+    pub amount: Amount,
     pub timestamp: u64,
 }
 
 impl IncomingEdgeRef {
-    pub fn new(src: NodeId, timestamp: u64) -> Self {
-        Self { src, timestamp }
+    pub fn new(id: EdgeId, src: NodeId, amount: Amount, timestamp: u64) -> Self {
+        Self {
+            id,
+            src,
+            amount,
+            timestamp,
+        }
     }
 }
 
@@ -260,80 +910,454 @@ mod tests {
     #[test]
     fn test_single_edge() {
         let mut gb = GraphBuilder::new(2);
-        gb.add_edge(0, 1, 2, 3);
+        gb.add_edge(0, 1, 2, 3).unwrap();
         let g = gb.freeze();
 
         assert_eq!(vec![0, 1, 1], g.offsets_out);
-        assert_eq!(Some(OutgoingEdgeRef::new(1, 2, 3)), g.edges_from(0).next());
+        assert_eq!(
+            Some(OutgoingEdgeRef::new(0, 1, 2, 3)),
+            g.edges_from(0).next()
+        );
         assert_eq!(None, g.edges_from(1).next());
 
-        assert_eq!(Some(IncomingEdgeRef::new(0, 3)), g.edges_to(1).next());
+        assert_eq!(Some(IncomingEdgeRef::new(0, 0, 2, 3)), g.edges_to(1).next());
         assert_eq!(None, g.edges_to(0).next());
     }
 
     #[test]
     fn test_single_source_edges() {
         let mut gb = GraphBuilder::new(4);
-        gb.add_edge(0, 1, 1, 2);
-        gb.add_edge(0, 2, 2, 3);
-        gb.add_edge(0, 3, 3, 4);
+        gb.add_edge(0, 1, 1, 2).unwrap();
+        gb.add_edge(0, 2, 2, 3).unwrap();
+        gb.add_edge(0, 3, 3, 4).unwrap();
         let g = gb.freeze();
 
         assert_eq!(vec![0, 3, 3, 3, 3], g.offsets_out);
         let mut iter = g.edges_from(0);
-        assert_eq!(Some(OutgoingEdgeRef::new(1, 1, 2)), iter.next());
-        assert_eq!(Some(OutgoingEdgeRef::new(2, 2, 3)), iter.next());
-        assert_eq!(Some(OutgoingEdgeRef::new(3, 3, 4)), iter.next());
+        assert_eq!(Some(OutgoingEdgeRef::new(0, 1, 1, 2)), iter.next());
+        assert_eq!(Some(OutgoingEdgeRef::new(1, 2, 2, 3)), iter.next());
+        assert_eq!(Some(OutgoingEdgeRef::new(2, 3, 3, 4)), iter.next());
         assert_eq!(None, iter.next());
     }
 
     #[test]
     fn test_single_destination_edges() {
         let mut gb = GraphBuilder::new(4);
-        gb.add_edge(1, 0, 1, 2);
-        gb.add_edge(2, 0, 2, 3);
-        gb.add_edge(3, 0, 3, 4);
+        gb.add_edge(1, 0, 1, 2).unwrap();
+        gb.add_edge(2, 0, 2, 3).unwrap();
+        gb.add_edge(3, 0, 3, 4).unwrap();
         let g = gb.freeze();
 
         assert_eq!(vec![0, 3, 3, 3, 3], g.offsets_in);
         let mut iter = g.edges_to(0);
-        assert_eq!(Some(IncomingEdgeRef::new(1, 2)), iter.next());
-        assert_eq!(Some(IncomingEdgeRef::new(2, 3)), iter.next());
-        assert_eq!(Some(IncomingEdgeRef::new(3, 4)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(0, 1, 1, 2)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(1, 2, 2, 3)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(2, 3, 3, 4)), iter.next());
         assert_eq!(None, iter.next());
     }
 
     #[test]
     fn test_multiple_edges() {
         let mut gb = GraphBuilder::new(3);
-        gb.add_edge(0, 2, 7, 8);
-        gb.add_edge(2, 0, 1, 2);
-        gb.add_edge(0, 1, 3, 4);
-        gb.add_edge(1, 2, 5, 6);
+        gb.add_edge(0, 2, 7, 8).unwrap();
+        gb.add_edge(2, 0, 1, 2).unwrap();
+        gb.add_edge(0, 1, 3, 4).unwrap();
+        gb.add_edge(1, 2, 5, 6).unwrap();
         let g = gb.freeze();
 
         assert_eq!(vec![0, 2, 3, 4], g.offsets_out);
         assert_eq!(vec![0, 1, 2, 4], g.offsets_in);
         let mut iter = g.edges_from(0);
-        assert_eq!(Some(OutgoingEdgeRef::new(2, 7, 8)), iter.next());
-        assert_eq!(Some(OutgoingEdgeRef::new(1, 3, 4)), iter.next());
+        assert_eq!(Some(OutgoingEdgeRef::new(0, 2, 7, 8)), iter.next());
+        assert_eq!(Some(OutgoingEdgeRef::new(1, 1, 3, 4)), iter.next());
         assert_eq!(None, iter.next());
         let mut iter = g.edges_from(1);
-        assert_eq!(Some(OutgoingEdgeRef::new(2, 5, 6)), iter.next());
+        assert_eq!(Some(OutgoingEdgeRef::new(2, 2, 5, 6)), iter.next());
         assert_eq!(None, iter.next());
         let mut iter = g.edges_from(2);
-        assert_eq!(Some(OutgoingEdgeRef::new(0, 1, 2)), iter.next());
+        assert_eq!(Some(OutgoingEdgeRef::new(3, 0, 1, 2)), iter.next());
         assert_eq!(None, iter.next());
 
         let mut iter = g.edges_to(0);
-        assert_eq!(Some(IncomingEdgeRef::new(2, 2)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(3, 2, 1, 2)), iter.next());
+        assert_eq!(None, iter.next());
+        let mut iter = g.edges_to(1);
+        assert_eq!(Some(IncomingEdgeRef::new(1, 0, 3, 4)), iter.next());
+        assert_eq!(None, iter.next());
+        let mut iter = g.edges_to(2);
+        assert_eq!(Some(IncomingEdgeRef::new(0, 0, 7, 8)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(2, 1, 5, 6)), iter.next());
+        assert_eq!(None, iter.next());
+    }
+
+    #[test]
+    fn test_sort_by_time() {
+        let mut gb = GraphBuilder::new(3);
+        gb.add_edge(0, 1, 1, 30).unwrap();
+        gb.add_edge(0, 2, 2, 10).unwrap();
+        gb.add_edge(2, 1, 3, 20).unwrap();
+        gb.add_edge(0, 1, 4, 20).unwrap();
+        gb.add_edge(2, 1, 5, 5).unwrap();
+        let g = gb.freeze_with(&FreezeConfig {
+            sort_by_time: true,
+            ..Default::default()
+        });
+
+        assert!(g.is_time_sorted());
+        let mut iter = g.edges_from(0);
+        assert_eq!(Some(OutgoingEdgeRef::new(0, 2, 2, 10)), iter.next());
+        assert_eq!(Some(OutgoingEdgeRef::new(1, 1, 4, 20)), iter.next());
+        assert_eq!(Some(OutgoingEdgeRef::new(2, 1, 1, 30)), iter.next());
         assert_eq!(None, iter.next());
         let mut iter = g.edges_to(1);
-        assert_eq!(Some(IncomingEdgeRef::new(0, 4)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(3, 2, 5, 5)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(1, 0, 4, 20)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(4, 2, 3, 20)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(2, 0, 1, 30)), iter.next());
         assert_eq!(None, iter.next());
+    }
+
+    #[test]
+    fn test_edges_between() {
+        for sort_by_time in [false, true] {
+            let mut gb = GraphBuilder::new(3);
+            gb.add_edge(0, 1, 1, 30).unwrap();
+            gb.add_edge(0, 2, 2, 10).unwrap();
+            gb.add_edge(0, 1, 3, 20).unwrap();
+            gb.add_edge(2, 1, 4, 15).unwrap();
+            let g = gb.freeze_with(&FreezeConfig {
+                sort_by_time,
+                ..Default::default()
+            });
+
+            let mut out = g
+                .edges_from_between(0, 10, 20)
+                .map(|e| (e.dst, e.amount, e.timestamp))
+                .collect::<Vec<_>>();
+            out.sort();
+            assert_eq!(vec![(1, 3, 20), (2, 2, 10)], out);
+            assert_eq!(0, g.edges_from_between(0, 31, 100).count());
+            assert_eq!(0, g.edges_from_between(1, 0, 100).count());
+
+            let mut inc = g
+                .edges_to_between(1, 16, 30)
+                .map(|e| (e.src, e.amount, e.timestamp))
+                .collect::<Vec<_>>();
+            inc.sort();
+            assert_eq!(vec![(0, 1, 30), (0, 3, 20)], inc);
+        }
+    }
+
+    #[test]
+    fn test_edge_ids_match_across_directions() {
+        let mut gb = GraphBuilder::new(4);
+        gb.add_edge(3, 1, 1, 1).unwrap();
+        gb.add_edge(2, 0, 2, 2).unwrap();
+        gb.add_edge(1, 3, 3, 3).unwrap();
+        gb.add_edge(3, 0, 4, 4).unwrap();
+        gb.add_edge(2, 3, 5, 5).unwrap();
+        gb.add_edge(0, 2, 6, 6).unwrap();
+        gb.add_edge(3, 2, 7, 7).unwrap();
+        gb.add_edge(1, 0, 8, 8).unwrap();
+        let g = gb.freeze();
+
+        let mut out = vec![];
+        let mut inc = vec![];
+        for n in 0..g.node_count() as u32 {
+            assert!(g.edges_from(n).all(|e| g.srcs_out[e.id as usize] == n));
+            out.extend(
+                g.edges_from(n)
+                    .map(|e| (e.id, n, e.dst, e.amount, e.timestamp)),
+            );
+            inc.extend(
+                g.edges_to(n)
+                    .map(|e| (e.id, e.src, n, e.amount, e.timestamp)),
+            );
+        }
+        inc.sort();
+        assert_eq!(8, out.len());
+        assert_eq!(out, inc);
+    }
+
+    #[test]
+    fn test_out_edges_grouped_by_source() {
+        let srcs = [3, 2, 1, 3, 2, 3, 0, 3, 3];
+        let mut gb = GraphBuilder::new(4);
+        for (i, src) in srcs.iter().enumerate() {
+            gb.add_edge(*src, 0, i as Amount, 0).unwrap();
+        }
+        let g = gb.freeze();
+
+        for n in 0..g.node_count() as u32 {
+            let expected = srcs
+                .iter()
+                .enumerate()
+                .filter(|(_, src)| **src == n)
+                .map(|(i, _)| i as Amount)
+                .collect::<Vec<_>>();
+            let actual = g.edges_from(n).map(|e| e.amount).collect::<Vec<_>>();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_attributes_follow_edges() {
+        let mut gb = GraphBuilder::new(3);
+        let fee = gb.add_attribute("fee", AttributeKind::U64).unwrap();
+        gb.add_edge(2, 0, 1, 30).unwrap();
+        let tx = gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+        gb.add_edge_with_attributes(
+            0,
+            1,
+            2,
+            20,
+            &[AttributeValue::U64(7), AttributeValue::Str("0xb")],
+        )
+        .unwrap();
+        gb.add_edge_with_attributes(
+            0,
+            2,
+            3,
+            10,
+            &[AttributeValue::U64(9), AttributeValue::Str("0xc")],
+        )
+        .unwrap();
+        let g = gb.freeze_with(&FreezeConfig {
+            sort_by_time: true,
+            ..Default::default()
+        });
+
+        assert_eq!(Some(tx), g.attribute_id("tx_hash"));
+        assert_eq!(None, g.attribute_id("block"));
+        let out = g
+            .edges_from(0)
+            .map(|e| (e.amount, g.attribute(fee, e.id), g.attribute(tx, e.id)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (3, AttributeValue::U64(9), AttributeValue::Str("0xc")),
+                (2, AttributeValue::U64(7), AttributeValue::Str("0xb")),
+            ],
+            out
+        );
+        let e = g.edges_to(0).next().unwrap();
+        assert_eq!(AttributeValue::U64(0), g.attribute(fee, e.id));
+        assert_eq!(AttributeValue::Str(""), g.attribute(tx, e.id));
+    }
+
+    #[test]
+    fn test_attribute_validation() {
+        let mut gb = GraphBuilder::new(2);
+        gb.add_attribute("fee", AttributeKind::U64).unwrap();
+
+        assert!(gb.add_attribute("fee", AttributeKind::Str).is_err());
+        assert!(gb.add_edge_with_attributes(0, 1, 1, 1, &[]).is_err());
+        assert!(
+            gb.add_edge_with_attributes(0, 1, 1, 1, &[AttributeValue::Str("x")])
+                .is_err()
+        );
+        assert_eq!(0, gb.freeze().edge_count());
+    }
+
+    #[test]
+    fn test_parallel_freeze_is_identical() {
+        use crate::core::ids::NodeRegistry;
+        use crate::core::snapshot::write_snapshot;
+        use crate::ingest::synthetic::{SyntheticConfig, generate};
+
+        let build = || {
+            let cfg = SyntheticConfig {
+                node_count: 500,
+                edge_count: 5_000,
+                seed: 7,
+            };
+            let mut gb = GraphBuilder::new(cfg.node_count as usize);
+            let eth = gb.asset_id("ETH").unwrap();
+            gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+            for (i, e) in generate(&cfg).enumerate() {
+                let tx = format!("0x{i:x}");
+                let asset = if i % 3 == 0 { eth } else { DEFAULT_ASSET };
+                let ts = e.timestamp % 1000;
+                gb.add_asset_edge(
+                    e.src,
+                    e.dst,
+                    asset,
+                    e.amount,
+                    ts,
+                    &[AttributeValue::Str(&tx)],
+                )
+                .unwrap();
+            }
+            gb
+        };
+        let to_bytes = |g: &Graph| {
+            let mut buf = Vec::new();
+            write_snapshot(&mut buf, g, &NodeRegistry::new()).unwrap();
+            buf
+        };
+
+        for sort_by_time in [false, true] {
+            let expected = to_bytes(&build().freeze_with(&FreezeConfig {
+                sort_by_time,
+                threads: 1,
+                ..Default::default()
+            }));
+            for threads in [2, 3, 8] {
+                let actual = to_bytes(&build().freeze_with(&FreezeConfig {
+                    sort_by_time,
+                    threads,
+                    ..Default::default()
+                }));
+                assert!(
+                    expected == actual,
+                    "threads: {threads}, sorted: {sort_by_time}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_freeze_small_graphs() {
+        let cfg = FreezeConfig {
+            threads: 4,
+            ..Default::default()
+        };
+        let g = GraphBuilder::new(0).freeze_with(&cfg);
+        assert_eq!(0, g.node_count());
+
+        let mut gb = GraphBuilder::new(3);
+        gb.add_edge(0, 2, 7, 8).unwrap();
+        gb.add_edge(2, 0, 1, 2).unwrap();
+        gb.add_edge(0, 1, 3, 4).unwrap();
+        gb.add_edge(1, 2, 5, 6).unwrap();
+        let g = gb.freeze_with(&cfg);
+
+        assert_eq!(vec![0, 2, 3, 4], g.offsets_out);
+        assert_eq!(vec![0, 1, 2, 4], g.offsets_in);
         let mut iter = g.edges_to(2);
-        assert_eq!(Some(IncomingEdgeRef::new(0, 8)), iter.next());
-        assert_eq!(Some(IncomingEdgeRef::new(1, 6)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(0, 0, 7, 8)), iter.next());
+        assert_eq!(Some(IncomingEdgeRef::new(2, 1, 5, 6)), iter.next());
         assert_eq!(None, iter.next());
     }
+
+    // Timing check, run with `cargo test --release -- --ignored` on a machine with 4 or more
+    // cores.
+    #[test]
+    #[ignore]
+    fn bench_parallel_freeze() {
+        use crate::ingest::synthetic::{SyntheticConfig, generate};
+        use std::time::{Duration, Instant};
+
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get().min(8));
+        if threads < 4 {
+            return;
+        }
+        let synthetic = SyntheticConfig {
+            node_count: 1_000_000,
+            edge_count: 10_000_000,
+            seed: 3,
+        };
+        let build = || {
+            let mut gb = GraphBuilder::new(synthetic.node_count as usize);
+            for e in generate(&synthetic) {
+                gb.add_edge(e.src, e.dst, e.amount, e.timestamp).unwrap();
+            }
+            gb
+        };
+        let time = |threads| {
+            (0..3)
+                .map(|_| {
+                    let gb = build();
+                    let start = Instant::now();
+                    gb.freeze_with(&FreezeConfig {
+                        sort_by_time: true,
+                        threads,
+                        ..Default::default()
+                    });
+                    start.elapsed()
+                })
+                .min()
+                .unwrap_or(Duration::MAX)
+        };
+
+        let (sequential, parallel) = (time(1), time(threads));
+        assert!(
+            parallel * 3 < sequential * 2,
+            "{threads} threads took {parallel:?}, one thread {sequential:?}"
+        );
+    }
+
+    #[test]
+    fn test_compressed_adjacency() {
+        use crate::ingest::synthetic::{SyntheticConfig, generate};
+
+        let cfg = SyntheticConfig {
+            node_count: 200,
+            edge_count: 5_000,
+            seed: 3,
+        };
+        for sort_by_time in [false, true] {
+            let build = |compress| {
+                let mut gb = GraphBuilder::new(cfg.node_count as usize);
+                for e in generate(&cfg) {
+                    gb.add_edge(e.src, e.dst, e.amount, e.timestamp).unwrap();
+                }
+                gb.freeze_with(&FreezeConfig {
+                    sort_by_time,
+                    compress,
+                    ..Default::default()
+                })
+            };
+            let plain = build(false);
+            let packed = build(true);
+
+            assert!(packed.is_compressed());
+            assert_eq!(1.0, plain.adjacency_compression_ratio());
+            assert!(packed.adjacency_compression_ratio() > 1.5);
+            let t0 = generate(&cfg).map(|e| e.timestamp).min().unwrap();
+            for n in 0..cfg.node_count {
+                assert!(plain.edges_from(n).eq(packed.edges_from(n)));
+                assert!(plain.edges_to(n).eq(packed.edges_to(n)));
+                assert_eq!(plain.edges_to(n).len(), packed.edges_to(n).len());
+                let (from, to) = (t0 + 20_000_000, t0 + 60_000_000);
+                assert!(
+                    plain
+                        .edges_from_between(n, from, to)
+                        .eq(packed.edges_from_between(n, from, to))
+                );
+                assert!(
+                    plain
+                        .edges_to_between(n, from, to)
+                        .eq(packed.edges_to_between(n, from, to))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_node_id_out_of_range() {
+        let mut gb = GraphBuilder::new(2);
+        assert!(gb.add_edge(0, 2, 1, 1).is_err());
+        assert!(gb.add_asset_edge(5, 1, DEFAULT_ASSET, 1, 1, &[]).is_err());
+        gb.add_edge(1, 0, 1, 1).unwrap();
+
+        let g = gb.freeze();
+        assert_eq!(1, g.edge_count());
+        assert_eq!(2, g.node_count());
+    }
+
+    #[test]
+    fn test_growable_builder() {
+        let mut gb = GraphBuilder::growable();
+        gb.add_edge(0, 3, 1, 1).unwrap();
+        gb.add_edge(7, 0, 2, 2).unwrap();
+        assert_eq!(8, gb.node_count());
+        gb.reserve_nodes(10);
+        gb.reserve_nodes(4);
+
+        let g = gb.freeze();
+        assert_eq!(10, g.node_count());
+        assert_eq!(1, g.in_degree(3));
+        assert_eq!(0, g.out_degree(9));
+    }
 }
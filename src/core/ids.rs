@@ -1,37 +1,231 @@
-use std::collections::HashMap;
+use anyhow::ensure;
+use std::hash::{BuildHasher, RandomState};
 
 pub type NodeId = u32;
 
+/// Position of an edge in the frozen out-CSR, shared by both traversal directions.
+pub type EdgeId = u64;
+
+pub type AssetId = u16;
+
+/// Asset of edges added without one, registered under an empty name.
+pub const DEFAULT_ASSET: AssetId = 0;
+
+/// Interned external ids, e.g. addresses, numbered densely in the order of registration.
+///
+/// The ids are stored back to back in one byte arena and indexed by an open addressing hash
+/// table of node ids, which takes about 16 bytes per node on top of the id itself.
 pub struct NodeRegistry {
-    map: HashMap<String, NodeId>,
+    pub(crate) arena: Vec<u8>,
+    // start of every id in `arena`, with the arena length appended
+    pub(crate) offsets: Vec<u64>,
+    // `NodeId + 1` of the id hashing to each slot or a later one, `0` for an empty slot; the
+    // number of slots is a power of two
+    pub(crate) slots: Vec<u32>,
+    hasher: RandomState,
+}
+
+impl Default for NodeRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NodeRegistry {
     pub fn new() -> Self {
         Self {
-            map: HashMap::new(),
+            arena: vec![],
+            offsets: vec![0],
+            slots: vec![],
+            hasher: RandomState::new(),
         }
     }
 
     pub fn get_or_insert(&mut self, external_id: &str) -> NodeId {
-        let next = self.map.len();
-        *self.map.entry(external_id.to_string()).or_insert({
-            if next == u32::MAX as usize {
-                panic!("Nodes count exceeds the limit")
-            }
-            next as u32
-        })
+        if let Some(node_id) = self.get(external_id) {
+            return node_id;
+        }
+        let next = self.len();
+        if next == u32::MAX as usize {
+            panic!("Nodes count exceeds the limit")
+        }
+        if (next + 1) * 4 > self.slots.len() * 3 {
+            self.grow();
+        }
+        self.arena.extend_from_slice(external_id.as_bytes());
+        self.offsets.push(self.arena.len() as u64);
+        let slot = self.free_slot(external_id.as_bytes());
+        self.slots[slot] = next as u32 + 1;
+        next as NodeId
     }
 
     pub fn get(&self, external_id: &str) -> Option<NodeId> {
-        self.map.get(external_id).copied()
+        if self.slots.is_empty() {
+            return None;
+        }
+        let mask = self.slots.len() - 1;
+        let mut slot = self.hash(external_id.as_bytes()) & mask;
+        loop {
+            match self.slots[slot] {
+                0 => return None,
+                entry if self.bytes(entry - 1) == external_id.as_bytes() => {
+                    return Some(entry - 1);
+                }
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+
+    /// External id of a registered node.
+    ///
+    /// # Panics
+    ///
+    /// If `node_id` was not returned by this registry.
+    pub fn external_id(&self, node_id: NodeId) -> &str {
+        // only ever written from `&str` or validated by `from_arena`
+        std::str::from_utf8(self.bytes(node_id)).expect("external ids are valid UTF-8")
+    }
+
+    /// All nodes with their external ids, ordered by `NodeId`.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (NodeId, &str)> {
+        (0..self.len() as NodeId).map(|node_id| (node_id, self.external_id(node_id)))
     }
 
     pub fn len(&self) -> usize {
-        self.map.len()
+        self.offsets.len() - 1
     }
 
     pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
+        self.len() == 0
+    }
+
+    pub(crate) fn from_external_ids<S: AsRef<str>>(
+        external_ids: impl IntoIterator<Item = S>,
+    ) -> Self {
+        let mut registry = Self::new();
+        for external_id in external_ids {
+            registry.get_or_insert(external_id.as_ref());
+        }
+        registry
+    }
+
+    /// Registry over ids stored back to back in `arena`, `offsets` holding the start of every
+    /// id and the arena length, e.g. the fields of a registry read back from a snapshot.
+    pub(crate) fn from_arena(offsets: Vec<u64>, arena: Vec<u8>) -> anyhow::Result<Self> {
+        ensure!(
+            offsets.first() == Some(&0)
+                && offsets.last() == Some(&(arena.len() as u64))
+                && offsets.is_sorted(),
+            "external id offsets are corrupted"
+        );
+        let mut registry = Self {
+            arena,
+            offsets,
+            slots: vec![],
+            hasher: RandomState::new(),
+        };
+        ensure!(
+            registry.len() <= u32::MAX as usize,
+            "Nodes count exceeds the limit"
+        );
+        registry.slots = vec![0; slot_count(registry.len())];
+        for node_id in 0..registry.len() as NodeId {
+            let external_id = std::str::from_utf8(registry.bytes(node_id))?;
+            ensure!(
+                registry.get(external_id).is_none(),
+                "external id {external_id} is registered twice"
+            );
+            let slot = registry.free_slot(external_id.as_bytes());
+            registry.slots[slot] = node_id + 1;
+        }
+        Ok(registry)
+    }
+
+    fn bytes(&self, node_id: NodeId) -> &[u8] {
+        let from = self.offsets[node_id as usize] as usize;
+        let to = self.offsets[node_id as usize + 1] as usize;
+        &self.arena[from..to]
+    }
+
+    fn hash(&self, bytes: &[u8]) -> usize {
+        self.hasher.hash_one(bytes) as usize
+    }
+
+    fn free_slot(&self, bytes: &[u8]) -> usize {
+        let mask = self.slots.len() - 1;
+        let mut slot = self.hash(bytes) & mask;
+        while self.slots[slot] != 0 {
+            slot = (slot + 1) & mask;
+        }
+        slot
+    }
+
+    fn grow(&mut self) {
+        self.slots = vec![0; (2 * self.slots.len()).max(MIN_SLOTS)];
+        for node_id in 0..self.len() as NodeId {
+            let slot = self.free_slot(self.bytes(node_id));
+            self.slots[slot] = node_id + 1;
+        }
+    }
+}
+
+const MIN_SLOTS: usize = 16;
+
+/// Hash table slots of a registry holding `node_count` ids, at most three quarters full.
+pub(crate) fn slot_count(node_count: usize) -> usize {
+    (node_count * 4)
+        .div_ceil(3)
+        .next_power_of_two()
+        .max(MIN_SLOTS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_lookup() {
+        let mut registry = NodeRegistry::new();
+        let b = registry.get_or_insert("0xbb");
+        let a = registry.get_or_insert("0xaa");
+        assert_eq!(b, registry.get_or_insert("0xbb"));
+
+        assert_eq!("0xaa", registry.external_id(a));
+        assert_eq!("0xbb", registry.external_id(b));
+        assert_eq!(
+            vec![(0, "0xbb"), (1, "0xaa")],
+            registry.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_index_grows() {
+        let mut registry = NodeRegistry::new();
+        for i in 0..10_000 {
+            assert_eq!(i, registry.get_or_insert(&format!("0x{i:040x}")));
+        }
+        assert_eq!(10_000, registry.len());
+        assert!(registry.slots.len() * 3 >= registry.len() * 4);
+        for i in (0..10_000).step_by(97) {
+            assert_eq!(Some(i), registry.get(&format!("0x{i:040x}")));
+        }
+        assert_eq!(None, registry.get("0x"));
+        assert_eq!(None, registry.get(""));
+    }
+
+    #[test]
+    fn test_from_arena() {
+        let registry = NodeRegistry::from_external_ids(["a", "", "bc"]);
+        let copy =
+            NodeRegistry::from_arena(registry.offsets.clone(), registry.arena.clone()).unwrap();
+        assert_eq!(
+            registry.iter().collect::<Vec<_>>(),
+            copy.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(1), copy.get(""));
+
+        assert!(NodeRegistry::from_arena(vec![0, 1, 2], b"aa".to_vec()).is_err());
+        assert!(NodeRegistry::from_arena(vec![0, 2, 1], b"ab".to_vec()).is_err());
+        assert!(NodeRegistry::from_arena(vec![0, 3], b"ab".to_vec()).is_err());
     }
 }
@@ -1,13 +1,369 @@
+use crate::core::adjacency::Adjacency;
+use crate::core::amount::Amount;
+use crate::core::column::Column;
+use crate::core::graph::{FreezeConfig, Graph, GraphBuilder};
+use crate::core::ids::{self, AssetId, EdgeId, NodeId, NodeRegistry};
 use crate::ingest::synthetic::SyntheticEdge;
+use anyhow::ensure;
 
+const MB: usize = 1024 * 1024;
+
+// Per edge: source, destination, amount, timestamp and asset in the builder, and the in-CSR
+// copy of source, amount, timestamp and edge id created by freeze.
+const BUILDER_EDGE_BYTES: usize =
+    2 * size_of::<NodeId>() + size_of::<Amount>() + size_of::<u64>() + size_of::<AssetId>();
+const IN_EDGE_BYTES: usize =
+    size_of::<NodeId>() + size_of::<Amount>() + size_of::<u64>() + size_of::<EdgeId>();
+// Group index, hash map entry, per group totals and the kept transfers of `aggregate_parallel`.
+const AGGREGATE_EDGE_BYTES: usize = 8 + 32 + 32 + 16;
+
+/// Bytes used by the parts of a structure.
+#[derive(Debug, Default)]
+pub struct MemoryReport {
+    pub parts: Vec<MemoryPart>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MemoryPart {
+    pub name: &'static str,
+    /// Allocated by this process.
+    pub heap_bytes: usize,
+    /// Served from a memory-mapped snapshot through the page cache, only resident while used.
+    pub mapped_bytes: usize,
+}
+
+impl MemoryReport {
+    pub fn heap_bytes(&self) -> usize {
+        self.parts.iter().map(|p| p.heap_bytes).sum()
+    }
+
+    pub fn mapped_bytes(&self) -> usize {
+        self.parts.iter().map(|p| p.mapped_bytes).sum()
+    }
+
+    fn part(&mut self, name: &'static str) -> &mut MemoryPart {
+        if let Some(idx) = self.parts.iter().position(|p| p.name == name) {
+            return &mut self.parts[idx];
+        }
+        self.parts.push(MemoryPart {
+            name,
+            heap_bytes: 0,
+            mapped_bytes: 0,
+        });
+        self.parts.last_mut().unwrap()
+    }
+
+    fn add_heap(&mut self, name: &'static str, bytes: usize) {
+        self.part(name).heap_bytes += bytes;
+    }
+
+    fn add_column<T>(&mut self, name: &'static str, column: &Column<T>) {
+        let part = self.part(name);
+        part.heap_bytes += column.heap_bytes();
+        part.mapped_bytes += column.mapped_bytes();
+    }
+
+    fn add_adjacency(&mut self, name: &'static str, adj: &Adjacency) {
+        match adj {
+            Adjacency::Plain { nodes, timestamps } => {
+                self.add_column(name, nodes);
+                self.add_column(name, timestamps);
+            }
+            Adjacency::Compressed { offsets, data } => {
+                self.add_column(name, offsets);
+                self.add_column(name, data);
+            }
+        }
+    }
+}
+
+pub trait MemoryUsage {
+    fn memory_report(&self) -> MemoryReport;
+
+    fn heap_bytes(&self) -> usize {
+        self.memory_report().heap_bytes()
+    }
+}
+
+impl MemoryUsage for Graph {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        report.add_column("offsets", &self.offsets_out);
+        report.add_column("offsets", &self.offsets_in);
+        report.add_column("sources", &self.srcs_out);
+        report.add_adjacency("out adjacency", &self.adj_out);
+        report.add_adjacency("in adjacency", &self.adj_in);
+        report.add_column("amounts", &self.amounts_out);
+        report.add_column("amounts", &self.amounts_in);
+        report.add_column("in edge ids", &self.edge_ids_in);
+        report.add_column("assets", &self.assets);
+        report.add_heap("assets", string_bytes(&self.asset_names));
+        for attr in &self.attributes {
+            report.add_column("attributes", &attr.values);
+            report.add_column("attributes", &attr.str_offsets);
+            report.add_column("attributes", &attr.str_data);
+        }
+        if let Some(aggregates) = &self.aggregates {
+            report.add_column("aggregates", &aggregates.last_timestamps);
+            report.add_column("aggregates", &aggregates.transfer_offsets);
+            report.add_column("aggregates", &aggregates.transfer_amounts);
+            report.add_column("aggregates", &aggregates.transfer_timestamps);
+            for values in &aggregates.transfer_attributes {
+                report.add_column("aggregates", values);
+            }
+        }
+        report
+    }
+}
+
+impl MemoryUsage for GraphBuilder {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        report.add_heap(
+            "edges",
+            size_of::<NodeId>() * (self.srcs.capacity() + self.dsts.capacity())
+                + size_of::<Amount>() * self.amounts.capacity()
+                + size_of::<u64>() * self.timestamps.capacity()
+                + size_of::<AssetId>() * self.assets.capacity(),
+        );
+        report.add_heap("assets", self.asset_table.heap_bytes());
+        report.add_heap(
+            "attributes",
+            self.attributes.iter().map(|a| a.heap_bytes()).sum(),
+        );
+        report
+    }
+}
+
+impl MemoryUsage for NodeRegistry {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        report.add_heap(
+            "index",
+            size_of::<u32>() * self.slots.capacity() + size_of::<u64>() * self.offsets.capacity(),
+        );
+        report.add_heap("external ids", self.arena.capacity());
+        report
+    }
+
+    fn heap_bytes(&self) -> usize {
+        size_of::<u32>() * self.slots.capacity()
+            + size_of::<u64>() * self.offsets.capacity()
+            + self.arena.capacity()
+    }
+}
+
+/// Upper estimate of the peak heap bytes of freezing a builder of `edge_count` edges without
+/// attributes, the builder included. The frozen graph itself takes about two thirds of it.
+pub fn predict_freeze_peak(node_count: usize, edge_count: usize, cfg: &FreezeConfig) -> usize {
+    // the out-CSR permutation in place, or the gather order of the parallel path
+    let mut edge_bytes = BUILDER_EDGE_BYTES + IN_EDGE_BYTES + size_of::<usize>();
+    if cfg.threads > 1 {
+        edge_bytes += size_of::<u64>();
+    }
+    if cfg.compress {
+        edge_bytes += 2 * (size_of::<NodeId>() + size_of::<u64>());
+    }
+    if cfg.aggregate_parallel {
+        edge_bytes += AGGREGATE_EDGE_BYTES;
+    }
+    // two offset arrays and a per node counter, with any number of threads
+    node_count * 3 * size_of::<usize>() + edge_count * edge_bytes
+}
+
+#[deprecated(note = "use `MemoryReport` and `predict_freeze_peak`")]
 pub struct MemoryStats {
     pub edges: usize,
     pub bytes: usize,
 }
 
+/// Bytes of `edges` raw synthetic edges.
+#[deprecated(note = "use `predict_freeze_peak`, which covers the whole build of a graph")]
+#[allow(deprecated)]
 pub fn estimate_edge_memory(edges: usize) -> MemoryStats {
     MemoryStats {
         edges,
         bytes: edges * size_of::<SyntheticEdge>(),
     }
 }
+
+/// Estimated heap bytes of a `NodeRegistry` holding `node_count` external ids.
+pub fn predict_registry_bytes(node_count: usize, avg_external_id_len: usize) -> usize {
+    size_of::<u32>() * ids::slot_count(node_count)
+        + node_count * (size_of::<u64>() + avg_external_id_len)
+}
+
+/// Limit on the predicted peak memory of ingest, see `GraphBuilder::set_memory_budget`.
+#[derive(Clone)]
+pub struct MemoryBudget {
+    pub limit_bytes: usize,
+    /// Configuration the graph will be frozen with, which determines the peak.
+    pub freeze: FreezeConfig,
+}
+
+impl MemoryBudget {
+    pub fn new(limit_bytes: usize) -> Self {
+        Self {
+            limit_bytes,
+            freeze: FreezeConfig::default(),
+        }
+    }
+
+    pub(crate) fn check(&self, needed_bytes: usize, edge_count: usize) -> anyhow::Result<()> {
+        ensure!(
+            needed_bytes <= self.limit_bytes,
+            "memory budget of {} MB exceeded: building a graph of {edge_count} edges needs about {} MB",
+            self.limit_bytes / MB,
+            needed_bytes.div_ceil(MB)
+        );
+        Ok(())
+    }
+}
+
+fn string_bytes(strings: &[String]) -> usize {
+    strings
+        .iter()
+        .map(|s| size_of::<String>() + s.capacity())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::attributes::{AttributeKind, AttributeValue};
+    use crate::core::graph::BUDGET_CHECK_EDGES;
+    use crate::ingest::synthetic::{SyntheticConfig, generate};
+
+    fn sample(cfg: &FreezeConfig) -> (GraphBuilder, Graph) {
+        let synthetic = SyntheticConfig {
+            node_count: 1_000,
+            edge_count: 20_000,
+            seed: 1,
+        };
+        let build = || {
+            let mut gb = GraphBuilder::new(synthetic.node_count as usize);
+            for e in generate(&synthetic) {
+                gb.add_edge(e.src, e.dst, e.amount, e.timestamp).unwrap();
+            }
+            gb
+        };
+        (build(), build().freeze_with(cfg))
+    }
+
+    #[test]
+    fn test_graph_report() {
+        let cfg = FreezeConfig::default();
+        let (gb, g) = sample(&cfg);
+        let report = g.memory_report();
+
+        let per_edge = BUILDER_EDGE_BYTES + IN_EDGE_BYTES;
+        let nodes = 2 * (g.node_count() + 1) * size_of::<usize>();
+        let assets = size_of::<String>();
+        assert_eq!(
+            g.edge_count() * per_edge + nodes + assets,
+            report.heap_bytes()
+        );
+        assert_eq!(0, report.mapped_bytes());
+        let offsets = MemoryPart {
+            name: "offsets",
+            heap_bytes: nodes,
+            mapped_bytes: 0,
+        };
+        assert_eq!(Some(&offsets), report.parts.first());
+
+        assert!(gb.heap_bytes() >= g.edge_count() * BUILDER_EDGE_BYTES);
+        assert!(predict_freeze_peak(g.node_count(), g.edge_count(), &cfg) > report.heap_bytes());
+    }
+
+    #[test]
+    fn test_parallel_peak_independent_of_threads() {
+        let peak = |threads| {
+            let cfg = FreezeConfig {
+                threads,
+                ..Default::default()
+            };
+            predict_freeze_peak(200_000_000, 1_000, &cfg)
+        };
+        assert_eq!(peak(2), peak(64));
+        assert_eq!(peak(1) + 1_000 * size_of::<u64>(), peak(64));
+    }
+
+    #[test]
+    fn test_compressed_report() {
+        let cfg = FreezeConfig {
+            compress: true,
+            ..Default::default()
+        };
+        let (_, g) = sample(&cfg);
+        let report = g.memory_report();
+
+        let adjacency = report
+            .parts
+            .iter()
+            .filter(|p| p.name.ends_with("adjacency"))
+            .map(|p| p.heap_bytes)
+            .sum::<usize>();
+        let plain = 2 * g.edge_count() * (size_of::<NodeId>() + size_of::<u64>());
+        let ratio = g.adjacency_compression_ratio();
+        assert!((plain as f64 / adjacency as f64 - ratio).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_registry_report() {
+        let mut registry = NodeRegistry::new();
+        for i in 0..1_000 {
+            registry.get_or_insert(&format!("addr{i:04}"));
+        }
+        let report = registry.memory_report();
+
+        assert!((8_000..16_000).contains(&report.parts[1].heap_bytes));
+        assert_eq!(report.heap_bytes(), registry.heap_bytes());
+        // the index takes at most 24 bytes per node on top of the ids
+        assert!(report.parts[0].heap_bytes <= 24 * 1_000);
+        let predicted = predict_registry_bytes(1_000, 8);
+        assert!(report.heap_bytes() >= predicted / 2 && report.heap_bytes() <= predicted * 2);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mut gb = GraphBuilder::new(10);
+        gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+        let budget = predict_freeze_peak(10, 50, &FreezeConfig::default()) + 4096;
+        gb.set_memory_budget(Some(MemoryBudget::new(budget)));
+
+        let mut added = 0;
+        while gb.add_edge(0, 1, 1, 1).is_ok() {
+            added += 1;
+        }
+        assert!((16..100).contains(&added));
+        let error = gb.add_edge(0, 1, 1, 1).unwrap_err().to_string();
+        assert!(error.starts_with("memory budget of 0 MB exceeded"));
+        assert_eq!(added, gb.freeze().edge_count());
+    }
+
+    #[test]
+    fn test_budget_checked_periodically() {
+        let mut gb = GraphBuilder::growable();
+        gb.reserve_edges(2 * BUDGET_CHECK_EDGES);
+        gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+        let budget = predict_freeze_peak(2, 2 * BUDGET_CHECK_EDGES, &FreezeConfig::default());
+        gb.set_memory_budget(Some(MemoryBudget::new(budget + 64 * 1024)));
+
+        let hash = AttributeValue::Str("0x0000000000000000000000000000000000000000");
+        let mut added = 0;
+        while gb.add_edge_with_attributes(0, 1, 1, 1, &[hash]).is_ok() {
+            added += 1;
+        }
+        // the strings outgrow the budget between two checks
+        assert!(added < 2 * BUDGET_CHECK_EDGES);
+        assert_eq!(0, (added + 1) % BUDGET_CHECK_EDGES);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_estimate_edge_memory() {
+        let stats = estimate_edge_memory(10);
+        assert_eq!(10, stats.edges);
+        assert_eq!(10 * size_of::<SyntheticEdge>(), stats.bytes);
+    }
+}
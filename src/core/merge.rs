@@ -0,0 +1,368 @@
+use crate::core::aggregate::Transfer;
+use crate::core::amount::Amount;
+use crate::core::assets::AssetScope;
+use crate::core::attributes::{AttributeKind, AttributeValue};
+use crate::core::graph::{FreezeConfig, Graph, GraphBuilder};
+use crate::core::ids::{AssetId, NodeId, NodeRegistry};
+use anyhow::{bail, ensure};
+use std::collections::HashMap;
+
+#[derive(Clone, Default)]
+pub struct MergeConfig {
+    /// Drop the edges of the right graph that are identical to an edge of the left one: same
+    /// endpoints, asset, amount, timestamp and attribute values. Each left edge cancels at most
+    /// one right edge, duplicates within one graph are kept.
+    pub dedup: bool,
+    pub freeze: FreezeConfig,
+}
+
+/// Graph combining the edges of two graphs over one node space.
+pub struct Merged {
+    pub graph: Graph,
+    /// Nodes of the left graph keep their ids, nodes only in the right graph follow in the
+    /// order of their ids there.
+    pub registry: NodeRegistry,
+    /// Edges of the right graph dropped as duplicates.
+    pub duplicates: usize,
+}
+
+// Edge in the node, asset and attribute space of the merged graph.
+#[derive(PartialEq, Eq, Hash)]
+struct EdgeKey<'a> {
+    src: NodeId,
+    dst: NodeId,
+    asset: AssetId,
+    amount: Amount,
+    timestamp: u64,
+    attributes: Vec<AttributeValue<'a>>,
+}
+
+/// Merges two graphs, e.g. built from separate ledger files, reconciling nodes by external id
+/// and assets and attributes by name. Aggregated graphs are expanded into their transfers, which
+/// `MergeConfig::freeze` may aggregate again.
+pub fn merge_graphs(
+    left: (&Graph, &NodeRegistry),
+    right: (&Graph, &NodeRegistry),
+    cfg: &MergeConfig,
+) -> anyhow::Result<Merged> {
+    for (graph, registry) in [left, right] {
+        ensure!(
+            graph.node_count() == registry.len(),
+            "graph has {} nodes but its registry {}",
+            graph.node_count(),
+            registry.len()
+        );
+    }
+    let (left_graph, right_graph) = (left.0, right.0);
+    let normalized = [left_graph, right_graph].map(|g| g.asset_scope() == AssetScope::Normalized);
+    if normalized[0] != normalized[1] {
+        bail!("cannot merge a normalized graph with a graph in base units");
+    }
+
+    ensure!(
+        left_graph.time_unit() == right_graph.time_unit(),
+        "cannot merge timestamps in {:?} with timestamps in {:?}",
+        left_graph.time_unit(),
+        right_graph.time_unit()
+    );
+
+    let mut registry = NodeRegistry::from_external_ids(left.1.iter().map(|(_, id)| id));
+    let right_nodes = right
+        .1
+        .iter()
+        .map(|(_, id)| registry.get_or_insert(id))
+        .collect::<Vec<_>>();
+
+    let mut gb = GraphBuilder::new(registry.len());
+    gb.asset_table = left_graph.asset_table();
+    gb.time_unit = left_graph.time_unit();
+    let right_table = right_graph.asset_table();
+    let mut right_assets = Vec::with_capacity(right_table.len());
+    for (right, name) in right_table.names.iter().enumerate() {
+        let asset = gb.asset_id(name)?;
+        match (
+            gb.asset_table.scale(asset),
+            right_table.scale(right as AssetId),
+        ) {
+            (Some(left), Some(decimals)) if left != decimals => {
+                bail!("asset {name} has {left} decimals in one graph and {decimals} in the other")
+            }
+            (None, Some(decimals)) => gb.set_asset_decimals(asset, decimals)?,
+            _ => {}
+        }
+        right_assets.push(asset);
+    }
+
+    let mut kinds = vec![];
+    for attr in left_graph
+        .attributes()
+        .iter()
+        .chain(right_graph.attributes())
+    {
+        match kinds.iter().find(|(name, _)| *name == attr.name()) {
+            Some((_, kind)) if *kind != attr.kind() => bail!(
+                "attribute {} is {:?} in one graph and {:?} in the other",
+                attr.name(),
+                kind,
+                attr.kind()
+            ),
+            Some(_) => {}
+            None => {
+                gb.add_attribute(attr.name(), attr.kind())?;
+                kinds.push((attr.name(), attr.kind()));
+            }
+        }
+    }
+
+    let mut seen = HashMap::new();
+    let mut duplicates = 0;
+    for (side, graph) in [left_graph, right_graph].into_iter().enumerate() {
+        let attr_ids = kinds
+            .iter()
+            .map(|(name, kind)| (graph.attribute_id(name), *kind))
+            .collect::<Vec<_>>();
+        for src in 0..graph.node_count() as NodeId {
+            for e in graph.edges_from(src) {
+                let (src, dst, asset) = if side == 0 {
+                    (src, e.dst, graph.edge_asset(e.id))
+                } else {
+                    (
+                        right_nodes[src as usize],
+                        right_nodes[e.dst as usize],
+                        right_assets[graph.edge_asset(e.id) as usize],
+                    )
+                };
+                let transfers = match graph.transfers(e.id) {
+                    Some(transfers) => transfers.collect(),
+                    None => vec![Transfer {
+                        amount: e.amount,
+                        timestamp: e.timestamp,
+                        attributes: (0..graph.attributes().len())
+                            .map(|attr| graph.attribute(attr, e.id))
+                            .collect(),
+                    }],
+                };
+                for transfer in transfers {
+                    // every transfer of an aggregated edge has its own values
+                    let attributes = attr_ids
+                        .iter()
+                        .map(|(attr, kind)| match (attr, kind) {
+                            (Some(attr), _) => transfer.attributes[*attr],
+                            (None, AttributeKind::U64) => AttributeValue::U64(0),
+                            (None, AttributeKind::Str) => AttributeValue::Str(""),
+                        })
+                        .collect::<Vec<_>>();
+                    if cfg.dedup {
+                        let key = EdgeKey {
+                            src,
+                            dst,
+                            asset,
+                            amount: transfer.amount,
+                            timestamp: transfer.timestamp,
+                            attributes: attributes.clone(),
+                        };
+                        if side == 0 {
+                            *seen.entry(key).or_insert(0usize) += 1;
+                        } else if let Some(count) = seen.get_mut(&key).filter(|c| **c > 0) {
+                            *count -= 1;
+                            duplicates += 1;
+                            continue;
+                        }
+                    }
+                    gb.add_asset_edge(
+                        src,
+                        dst,
+                        asset,
+                        transfer.amount,
+                        transfer.timestamp,
+                        &attributes,
+                    )?;
+                }
+            }
+        }
+    }
+
+    let mut graph = gb.try_freeze_with(&cfg.freeze)?;
+    if normalized[0] {
+        graph.asset_scope = AssetScope::Normalized;
+    }
+    Ok(Merged {
+        graph,
+        registry,
+        duplicates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ids::DEFAULT_ASSET;
+
+    fn ledger(edges: &[(&str, &str, &str, Amount, u64)]) -> (Graph, NodeRegistry) {
+        let mut registry = NodeRegistry::new();
+        let mut gb = GraphBuilder::growable();
+        gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+        for (src, dst, asset, amount, ts) in edges {
+            let src = registry.get_or_insert(src);
+            let dst = registry.get_or_insert(dst);
+            let asset = gb.asset_id(asset).unwrap();
+            let hash = format!("{ts}");
+            gb.add_asset_edge(src, dst, asset, *amount, *ts, &[AttributeValue::Str(&hash)])
+                .unwrap();
+        }
+        (gb.freeze(), registry)
+    }
+
+    #[test]
+    fn test_merge_reconciles_ids() {
+        let january = ledger(&[("a", "b", "", 5, 1), ("b", "c", "USDT", 7, 2)]);
+        let february = ledger(&[("c", "d", "", 3, 3), ("b", "c", "USDT", 7, 2)]);
+        let merged = merge_graphs(
+            (&january.0, &january.1),
+            (&february.0, &february.1),
+            &MergeConfig::default(),
+        )
+        .unwrap();
+
+        let g = &merged.graph;
+        assert_eq!(4, g.node_count());
+        assert_eq!(4, g.edge_count());
+        assert_eq!(0, merged.duplicates);
+        let [b, c, d] = ["b", "c", "d"].map(|id| merged.registry.get(id).unwrap());
+        assert_eq!(3, d);
+        assert_eq!(
+            vec![c, c],
+            g.edges_from(b).map(|e| e.dst).collect::<Vec<_>>()
+        );
+        let usdt = g.asset_id("USDT").unwrap();
+        assert!(g.edges_from(b).all(|e| g.edge_asset(e.id) == usdt));
+        let e = g.edges_from(c).next().unwrap();
+        assert_eq!((d, DEFAULT_ASSET), (e.dst, g.edge_asset(e.id)));
+        assert_eq!(AttributeValue::Str("3"), g.attribute(0, e.id));
+    }
+
+    #[test]
+    fn test_merge_dedup() {
+        let january = ledger(&[("a", "b", "", 5, 1), ("a", "b", "", 5, 1)]);
+        let february = ledger(&[
+            ("a", "b", "", 5, 1),
+            ("a", "b", "", 5, 1),
+            ("a", "b", "", 5, 1),
+        ]);
+        let merged = merge_graphs(
+            (&january.0, &january.1),
+            (&february.0, &february.1),
+            &MergeConfig {
+                dedup: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(2, merged.duplicates);
+        assert_eq!(3, merged.graph.edge_count());
+    }
+
+    #[test]
+    fn test_merge_aggregated_transfers() {
+        let aggregated = |edges: &[(&str, &str, &str, Amount, u64)]| {
+            let (g, registry) = ledger(edges);
+            let mut gb = GraphBuilder::new(g.node_count());
+            gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+            for src in 0..g.node_count() as NodeId {
+                for e in g.edges_from(src) {
+                    gb.add_edge_with_attributes(
+                        src,
+                        e.dst,
+                        e.amount,
+                        e.timestamp,
+                        &[g.attribute(0, e.id)],
+                    )
+                    .unwrap();
+                }
+            }
+            let cfg = FreezeConfig {
+                aggregate_parallel: true,
+                ..Default::default()
+            };
+            (gb.try_freeze_with(&cfg).unwrap(), registry)
+        };
+        // the second transfers have the same amount but different hashes, the third ones match
+        let january = aggregated(&[
+            ("a", "b", "", 5, 1),
+            ("a", "b", "", 5, 2),
+            ("a", "b", "", 4, 3),
+        ]);
+        let february = aggregated(&[
+            ("a", "b", "", 5, 1),
+            ("a", "b", "", 5, 9),
+            ("a", "b", "", 4, 3),
+        ]);
+        let merged = merge_graphs(
+            (&january.0, &january.1),
+            (&february.0, &february.1),
+            &MergeConfig {
+                dedup: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(2, merged.duplicates);
+        let g = &merged.graph;
+        let hashes = g
+            .edges_from(0)
+            .map(|e| (g.attribute(0, e.id), e.timestamp))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (AttributeValue::Str("1"), 1),
+                (AttributeValue::Str("2"), 2),
+                (AttributeValue::Str("3"), 3),
+                (AttributeValue::Str("9"), 9),
+            ],
+            hashes
+        );
+    }
+
+    #[test]
+    fn test_merge_rejects_conflicting_attributes() {
+        let (left, left_registry) = ledger(&[]);
+        let mut gb = GraphBuilder::new(0);
+        gb.add_attribute("tx_hash", AttributeKind::U64).unwrap();
+        let right = gb.freeze();
+        let result = merge_graphs(
+            (&left, &left_registry),
+            (&right, &NodeRegistry::new()),
+            &MergeConfig::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_merge_asset_scales() {
+        let graph = |decimals: Option<u8>| {
+            let mut gb = GraphBuilder::new(0);
+            let eth = gb.asset_id("ETH").unwrap();
+            if let Some(decimals) = decimals {
+                gb.set_asset_decimals(eth, decimals).unwrap();
+            }
+            gb.freeze()
+        };
+        let registry = NodeRegistry::new();
+        let merge = |left: &Graph, right: &Graph| {
+            merge_graphs(
+                (left, &registry),
+                (right, &registry),
+                &MergeConfig::default(),
+            )
+        };
+
+        let merged = merge(&graph(None), &graph(Some(18))).unwrap().graph;
+        let eth = merged.asset_id("ETH").unwrap();
+        assert_eq!(Some(18), merged.asset_table().scale(eth));
+        let merged = merge(&graph(None), &graph(None)).unwrap().graph;
+        assert_eq!(None, merged.asset_table().scale(eth));
+        assert!(merge(&graph(Some(6)), &graph(Some(18))).is_err());
+    }
+}
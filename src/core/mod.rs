@@ -1,3 +1,17 @@
+pub(crate) mod adjacency;
+pub mod aggregate;
+pub mod amount;
+pub mod assets;
+pub mod attributes;
+pub mod column;
+pub mod entity;
+pub mod external;
 pub mod graph;
 pub mod ids;
 pub mod memory;
+pub mod merge;
+pub(crate) mod parallel;
+pub mod snapshot;
+pub mod subgraph;
+pub mod time;
+pub mod view;
@@ -0,0 +1,244 @@
+use crate::core::ids::NodeId;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// Building blocks of the multi-threaded freeze. Keys are split into contiguous chunks and nodes
+// into ranges, so every thread does a share of the work, and placement within a node keeps the
+// input order, which makes the result identical to the single-threaded freeze.
+
+// Node ids are counted in at most this many blocks of consecutive ids to split the nodes into
+// ranges of similar edge counts, so the per thread counts do not grow with the node count.
+const MAX_BLOCKS: usize = 4096;
+
+/// Offsets of the keys grouped by node, and for every output position the index of the key
+/// placed there, kept in input order within a node. Every thread counts its own chunk of the
+/// keys per block of node ids, which splits the nodes into one range per thread; the chunks then
+/// group their keys by range and every thread orders the keys of its range by node. Besides the
+/// result this takes one index per key and one counter per node, whatever the thread count.
+pub(crate) fn bucket_order(
+    keys: &[NodeId],
+    node_count: usize,
+    threads: usize,
+) -> (Vec<usize>, Vec<usize>) {
+    let chunk = keys.len().div_ceil(threads).max(1);
+    let block_size = node_count.div_ceil(MAX_BLOCKS).max(1);
+    let block_count = node_count.div_ceil(block_size);
+    let histograms = thread::scope(|s| {
+        let handles = keys
+            .chunks(chunk)
+            .map(|part| {
+                s.spawn(move || {
+                    let mut counts = vec![0; block_count];
+                    for key in part {
+                        counts[*key as usize / block_size] += 1;
+                    }
+                    counts
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<Vec<usize>>>()
+    });
+
+    // Cuts the blocks into node ranges of similar edge counts, one per thread.
+    let mut bounds = vec![0];
+    let mut seen = 0;
+    for block in 0..block_count {
+        seen += histograms.iter().map(|h| h[block]).sum::<usize>();
+        if bounds.len() < threads && seen * threads >= keys.len() * bounds.len() {
+            bounds.push(block + 1);
+        }
+    }
+    bounds.push(block_count);
+    let ranges = bounds.len() - 1;
+    let mut range_of_block = vec![0; block_count];
+    for r in 0..ranges {
+        range_of_block[bounds[r]..bounds[r + 1]].fill(r);
+    }
+
+    // Position of every chunk's first key of a range, with the ranges in node order and the
+    // chunks in input order within a range.
+    let mut cursors = vec![vec![0; ranges]; histograms.len()];
+    let mut range_starts = Vec::with_capacity(ranges + 1);
+    let mut next = 0;
+    for r in 0..ranges {
+        range_starts.push(next);
+        for (cursor, histogram) in cursors.iter_mut().zip(&histograms) {
+            cursor[r] = next;
+            next += histogram[bounds[r]..bounds[r + 1]].iter().sum::<usize>();
+        }
+    }
+    range_starts.push(next);
+    drop(histograms);
+
+    let grouped = (0..keys.len())
+        .map(|_| AtomicUsize::new(0))
+        .collect::<Vec<_>>();
+    thread::scope(|s| {
+        for (i, (part, mut next)) in keys.chunks(chunk).zip(cursors).enumerate() {
+            let (grouped, range_of_block) = (&grouped, &range_of_block);
+            s.spawn(move || {
+                for (e, key) in part.iter().enumerate() {
+                    let r = range_of_block[*key as usize / block_size];
+                    grouped[next[r]].store(i * chunk + e, Ordering::Relaxed);
+                    next[r] += 1;
+                }
+            });
+        }
+    });
+    let grouped = grouped
+        .into_iter()
+        .map(AtomicUsize::into_inner)
+        .collect::<Vec<_>>();
+
+    let mut offsets = vec![0; node_count + 1];
+    let mut order = vec![0; keys.len()];
+    thread::scope(|s| {
+        let (mut offsets_rest, mut order_rest) = (&mut offsets[1..], &mut order[..]);
+        for r in 0..ranges {
+            let nodes = (bounds[r] * block_size).min(node_count)
+                ..(bounds[r + 1] * block_size).min(node_count);
+            let (offsets_part, rest) = std::mem::take(&mut offsets_rest).split_at_mut(nodes.len());
+            offsets_rest = rest;
+            let range = range_starts[r]..range_starts[r + 1];
+            let (order_part, rest) = std::mem::take(&mut order_rest).split_at_mut(range.len());
+            order_rest = rest;
+            let group = &grouped[range.clone()];
+            s.spawn(move || {
+                let mut next = vec![0; nodes.len()];
+                for e in group {
+                    next[keys[*e] as usize - nodes.start] += 1;
+                }
+                let mut end = range.start;
+                for (count, offset) in next.iter_mut().zip(offsets_part) {
+                    let start = end;
+                    end += *count;
+                    *count = start - range.start;
+                    *offset = end;
+                }
+                for e in group {
+                    let n = keys[*e] as usize - nodes.start;
+                    order_part[next[n]] = *e;
+                    next[n] += 1;
+                }
+            });
+        }
+    });
+    (offsets, order)
+}
+
+/// Stable sort of every node's slice of `order` by `key`.
+pub(crate) fn sort_buckets_by_key<F>(order: &mut [usize], offsets: &[usize], key: F, threads: usize)
+where
+    F: Fn(usize) -> u64 + Sync,
+{
+    let key = &key;
+    thread::scope(|s| {
+        for (nodes, part) in split_by_edges(order, offsets, threads) {
+            s.spawn(move || {
+                let base = offsets[nodes.start];
+                for n in nodes {
+                    part[offsets[n] - base..offsets[n + 1] - base].sort_by_key(|e| key(*e));
+                }
+            });
+        }
+    });
+}
+
+pub(crate) fn gather<T: Copy + Default + Send + Sync>(
+    values: &[T],
+    order: &[usize],
+    threads: usize,
+) -> Vec<T> {
+    let mut result = vec![T::default(); order.len()];
+    let chunk = order.len().div_ceil(threads).max(1);
+    thread::scope(|s| {
+        for (out, idx) in result.chunks_mut(chunk).zip(order.chunks(chunk)) {
+            s.spawn(move || {
+                for (value, i) in out.iter_mut().zip(idx) {
+                    *value = values[*i];
+                }
+            });
+        }
+    });
+    result
+}
+
+// Splits `values` into per-thread parts at node boundaries, each with a similar number of edges.
+fn split_by_edges<'a, T>(
+    mut values: &'a mut [T],
+    offsets: &[usize],
+    threads: usize,
+) -> Vec<(Range<usize>, &'a mut [T])> {
+    let node_count = offsets.len() - 1;
+    let edge_count = offsets[node_count];
+    let mut parts = Vec::with_capacity(threads);
+    let mut from = 0;
+    for t in 1..=threads {
+        let to = if t == threads {
+            node_count
+        } else {
+            let target = edge_count * t / threads;
+            (offsets.partition_point(|o| *o <= target) - 1).clamp(from, node_count)
+        };
+        let (part, rest) = values.split_at_mut(offsets[to] - offsets[from]);
+        parts.push((from..to, part));
+        values = rest;
+        from = to;
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_bucket_order_is_stable() {
+        let keys = [2, 0, 2, 1, 0, 2];
+        for threads in 1..8 {
+            assert_eq!(
+                (vec![0, 2, 3, 6, 6], vec![1, 4, 3, 0, 2, 5]),
+                bucket_order(&keys, 4, threads)
+            );
+        }
+        assert_eq!((vec![0, 0, 0], vec![]), bucket_order(&[], 2, 3));
+    }
+
+    #[test]
+    fn test_bucket_order_many_threads_and_nodes() {
+        // a histogram of all nodes per thread would take 16 GB here
+        let node_count = 1 << 22;
+        let keys = (0..10_000u32)
+            .map(|i| i.wrapping_mul(2_654_435_761) % node_count as u32)
+            .chain([7; 1_000])
+            .collect::<Vec<_>>();
+        let (offsets, order) = bucket_order(&keys, node_count, 512);
+
+        assert_eq!(node_count + 1, offsets.len());
+        assert_eq!(keys.len(), offsets[node_count]);
+        for n in [0, 7, 12_345, node_count - 1] {
+            let bucket = &order[offsets[n]..offsets[n + 1]];
+            assert!(bucket.iter().all(|e| keys[*e] as usize == n));
+            assert!(bucket.is_sorted());
+        }
+        assert!(offsets[8] - offsets[7] >= 1_000);
+        assert_eq!(keys.len(), order.iter().collect::<HashSet<_>>().len());
+    }
+
+    #[test]
+    fn test_sort_buckets_and_gather() {
+        let offsets = [0, 3, 3, 5];
+        let values = [30, 10, 20, 50, 40];
+        for threads in 1..4 {
+            let mut order = vec![0, 1, 2, 3, 4];
+            sort_buckets_by_key(&mut order, &offsets, |e| values[e], threads);
+            assert_eq!(vec![1, 2, 0, 4, 3], order);
+            assert_eq!(vec![10, 20, 30, 40, 50], gather(&values, &order, threads));
+        }
+    }
+}
@@ -0,0 +1,1268 @@
+use crate::core::adjacency::Adjacency;
+use crate::core::aggregate::Aggregates;
+use crate::core::amount::Amount;
+use crate::core::assets::AssetScope;
+use crate::core::attributes::{AttributeColumn, kind_from_u8, kind_to_u8};
+use crate::core::column::{Column, MappedSlice, valid_offsets};
+use crate::core::graph::Graph;
+use crate::core::ids::{AssetId, EdgeId, NodeId, NodeRegistry};
+use crate::core::time::TimeUnit;
+use anyhow::{bail, ensure};
+use bytemuck::Pod;
+use memmap2::Mmap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Layout: header, sections in a fixed order, trailer.
+// header:  magic[8] | version u32 | endian marker u32 | flags u64 | node count u64 | edge count u64,
+//          zero padded to 16 bytes
+// section: tag u32 | element size u32 | element count u64 | data, zero padded to 16 bytes
+// trailer: crc32 of everything before it, u32
+// All integers are stored in the native byte order of the writer.
+const MAGIC: &[u8; 8] = b"TRACELOC";
+const VERSION: u32 = 11;
+const ENDIAN_MARKER: u32 = 0x0102_0304;
+// sections start aligned for the u128 amounts, relative to a page aligned mapping
+const ALIGN: usize = 16;
+// columns of a stream of unknown size grow by at most this many bytes per read
+const READ_CHUNK: usize = 1 << 20;
+
+const FLAG_TIME_SORTED: u64 = 1;
+const FLAG_COMPRESSED: u64 = 2;
+const FLAG_AGGREGATED: u64 = 4;
+
+const TAG_OFFSETS_OUT: u32 = 1;
+const TAG_OFFSETS_IN: u32 = 2;
+const TAG_SRCS_OUT: u32 = 3;
+const TAG_DSTS: u32 = 4;
+const TAG_AMOUNTS_OUT: u32 = 5;
+const TAG_TIMESTAMPS_OUT: u32 = 6;
+const TAG_SRCS_IN: u32 = 7;
+const TAG_TIMESTAMPS_IN: u32 = 8;
+const TAG_REGISTRY_OFFSETS: u32 = 9;
+const TAG_REGISTRY_DATA: u32 = 10;
+const TAG_AMOUNTS_IN: u32 = 11;
+const TAG_EDGE_IDS_IN: u32 = 12;
+const TAG_ATTRIBUTE_KINDS: u32 = 13;
+const TAG_ATTRIBUTE_NAME: u32 = 14;
+const TAG_ATTRIBUTE_VALUES: u32 = 15;
+const TAG_ATTRIBUTE_STR_OFFSETS: u32 = 16;
+const TAG_ATTRIBUTE_STR_DATA: u32 = 17;
+const TAG_ASSETS: u32 = 18;
+const TAG_ASSET_NAME_OFFSETS: u32 = 19;
+const TAG_ASSET_NAME_DATA: u32 = 20;
+const TAG_ASSET_SCOPE: u32 = 21;
+const TAG_PACKED_OFFSETS_OUT: u32 = 22;
+const TAG_PACKED_DATA_OUT: u32 = 23;
+const TAG_PACKED_OFFSETS_IN: u32 = 24;
+const TAG_PACKED_DATA_IN: u32 = 25;
+const TAG_LAST_TIMESTAMPS: u32 = 26;
+const TAG_TRANSFER_OFFSETS: u32 = 27;
+const TAG_TRANSFER_AMOUNTS: u32 = 28;
+const TAG_TRANSFER_TIMESTAMPS: u32 = 29;
+const TAG_ASSET_DECIMALS: u32 = 30;
+const TAG_TIME_UNIT: u32 = 31;
+const TAG_TRANSFER_ATTRIBUTE_VALUES: u32 = 32;
+const TAG_ASSET_SCALED: u32 = 33;
+
+const SCOPE_SINGLE: u32 = 0;
+const SCOPE_MIXED: u32 = 1;
+const SCOPE_NORMALIZED: u32 = 2;
+
+const UNIT_SECONDS: u32 = 0;
+const UNIT_MILLISECONDS: u32 = 1;
+const UNIT_BLOCKS: u32 = 2;
+
+/// Writes the snapshot to a temporary file next to `path` and renames it over `path`, so that
+/// processes mapping the previous snapshot keep reading it.
+pub fn save_snapshot(
+    path: impl AsRef<Path>,
+    graph: &Graph,
+    registry: &NodeRegistry,
+) -> anyhow::Result<()> {
+    replace_file(path.as_ref(), |writer| {
+        write_snapshot(writer, graph, registry)
+    })
+}
+
+// Writes a file under a temporary name in the directory of `path`, syncs it and renames it over
+// `path`. A mapping of the file it replaces keeps the old inode instead of seeing a truncated or
+// half written file.
+pub(crate) fn replace_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("{} is not a file path", path.display()))?
+        .to_os_string();
+    name.push(format!(".tmp-{}", std::process::id()));
+    let tmp = TempFile(path.with_file_name(name));
+
+    let mut writer = BufWriter::new(File::create(&tmp.0)?);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp.0, path)?;
+    Ok(())
+}
+
+// A temporary file, removed when dropped unless it was renamed into place.
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+pub fn load_snapshot(path: impl AsRef<Path>) -> anyhow::Result<(Graph, NodeRegistry)> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len() as usize;
+    decode(StreamSource {
+        inner: BufReader::new(file),
+        hasher: crc32fast::Hasher::new(),
+        pos: 0,
+        size: Some(size),
+    })
+}
+
+/// Maps a snapshot into memory without copying the edge arrays; pages are loaded on access and
+/// shared through the page cache between processes mapping the same file. The checksum is not
+/// verified here, use `verify_snapshot` for that. The offsets, the ids in the edge arrays and
+/// the attribute strings are still checked, which reads them once, so a truncated or corrupted
+/// file fails here instead of on access.
+///
+/// The file must not be modified in place while the graph is alive; replacing it with
+/// `save_snapshot` is safe.
+pub fn map_snapshot(path: impl AsRef<Path>) -> anyhow::Result<(Graph, NodeRegistry)> {
+    let file = File::open(path)?;
+    // SAFETY: the mapping is read-only and `save_snapshot` replaces a snapshot by renaming a new
+    // file over it, which leaves this inode intact. This assumes that no other program truncates
+    // or edits the file in place while it is mapped.
+    let map = unsafe { Mmap::map(&file)? };
+    decode(MappedSource {
+        map: Arc::new(map),
+        pos: 0,
+    })
+}
+
+pub fn verify_snapshot(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let file = File::open(path)?;
+    // SAFETY: see `map_snapshot`
+    let map = unsafe { Mmap::map(&file)? };
+    ensure!(map.len() >= 4, "snapshot is truncated");
+    let (payload, checksum) = map.split_at(map.len() - 4);
+    ensure!(
+        u32::from_ne_bytes(checksum.try_into()?) == crc32fast::hash(payload),
+        "snapshot checksum mismatch, the file is corrupted"
+    );
+    Ok(())
+}
+
+pub fn write_snapshot<W: Write>(
+    writer: W,
+    graph: &Graph,
+    registry: &NodeRegistry,
+) -> anyhow::Result<()> {
+    let mut flags = 0;
+    if graph.is_time_sorted() {
+        flags |= FLAG_TIME_SORTED;
+    }
+    if graph.is_compressed() {
+        flags |= FLAG_COMPRESSED;
+    }
+    if graph.is_aggregated() {
+        flags |= FLAG_AGGREGATED;
+    }
+    write_parts(
+        writer,
+        &SnapshotParts {
+            flags,
+            node_count: graph.node_count(),
+            edge_count: graph.edge_count(),
+            offsets_out: &graph.offsets_out,
+            offsets_in: &graph.offsets_in,
+            srcs_out: SectionData::Memory(&graph.srcs_out),
+            amounts_out: SectionData::Memory(&graph.amounts_out),
+            adj_out: AdjacencyData::Memory(&graph.adj_out),
+            amounts_in: SectionData::Memory(&graph.amounts_in),
+            adj_in: AdjacencyData::Memory(&graph.adj_in),
+            edge_ids_in: SectionData::Memory(&graph.edge_ids_in),
+            assets: SectionData::Memory(&graph.assets),
+            asset_names: &graph.asset_names,
+            asset_decimals: &graph.asset_decimals,
+            asset_scaled: &graph.asset_scaled,
+            aggregates: graph.aggregates.as_ref(),
+            asset_scope: graph.asset_scope,
+            time_unit: graph.time_unit,
+            attributes: &graph.attributes,
+            registry,
+        },
+    )
+}
+
+/// Edge columns of a graph built outside of memory, one raw native-endian file per section.
+pub(crate) struct ColumnFiles {
+    pub(crate) srcs_out: PathBuf,
+    pub(crate) amounts_out: PathBuf,
+    pub(crate) dsts: PathBuf,
+    pub(crate) timestamps_out: PathBuf,
+    pub(crate) amounts_in: PathBuf,
+    pub(crate) srcs_in: PathBuf,
+    pub(crate) timestamps_in: PathBuf,
+    pub(crate) edge_ids_in: PathBuf,
+    pub(crate) assets: PathBuf,
+}
+
+pub(crate) struct SpilledGraph<'a> {
+    pub(crate) node_count: usize,
+    pub(crate) edge_count: usize,
+    pub(crate) time_sorted: bool,
+    pub(crate) offsets_out: &'a [usize],
+    pub(crate) offsets_in: &'a [usize],
+    pub(crate) columns: &'a ColumnFiles,
+    pub(crate) asset_names: &'a [String],
+    pub(crate) asset_decimals: &'a [u8],
+    pub(crate) asset_scaled: &'a [bool],
+    pub(crate) asset_scope: AssetScope,
+    pub(crate) time_unit: TimeUnit,
+}
+
+// Same layout as `write_snapshot` for an uncompressed graph without attributes, with the edge
+// columns streamed from their files.
+pub(crate) fn write_spilled_snapshot<W: Write>(
+    writer: W,
+    graph: &SpilledGraph,
+    registry: &NodeRegistry,
+) -> anyhow::Result<()> {
+    let columns = graph.columns;
+    let flags = if graph.time_sorted {
+        FLAG_TIME_SORTED
+    } else {
+        0
+    };
+    write_parts(
+        writer,
+        &SnapshotParts {
+            flags,
+            node_count: graph.node_count,
+            edge_count: graph.edge_count,
+            offsets_out: graph.offsets_out,
+            offsets_in: graph.offsets_in,
+            srcs_out: SectionData::File(&columns.srcs_out),
+            amounts_out: SectionData::File(&columns.amounts_out),
+            adj_out: AdjacencyData::Files {
+                nodes: &columns.dsts,
+                timestamps: &columns.timestamps_out,
+            },
+            amounts_in: SectionData::File(&columns.amounts_in),
+            adj_in: AdjacencyData::Files {
+                nodes: &columns.srcs_in,
+                timestamps: &columns.timestamps_in,
+            },
+            edge_ids_in: SectionData::File(&columns.edge_ids_in),
+            assets: SectionData::File(&columns.assets),
+            asset_names: graph.asset_names,
+            asset_decimals: graph.asset_decimals,
+            asset_scaled: graph.asset_scaled,
+            aggregates: None,
+            asset_scope: graph.asset_scope,
+            time_unit: graph.time_unit,
+            attributes: &[],
+            registry,
+        },
+    )
+}
+
+// A section held in memory, or in a file of its raw elements.
+enum SectionData<'a, T> {
+    Memory(&'a [T]),
+    File(&'a Path),
+}
+
+// Neighbour ids and timestamps of one direction.
+enum AdjacencyData<'a> {
+    Memory(&'a Adjacency),
+    Files {
+        nodes: &'a Path,
+        timestamps: &'a Path,
+    },
+}
+
+// Contents of a snapshot, whether the graph is in memory or spilled to column files.
+struct SnapshotParts<'a> {
+    flags: u64,
+    node_count: usize,
+    edge_count: usize,
+    offsets_out: &'a [usize],
+    offsets_in: &'a [usize],
+    srcs_out: SectionData<'a, NodeId>,
+    amounts_out: SectionData<'a, Amount>,
+    adj_out: AdjacencyData<'a>,
+    amounts_in: SectionData<'a, Amount>,
+    adj_in: AdjacencyData<'a>,
+    edge_ids_in: SectionData<'a, EdgeId>,
+    assets: SectionData<'a, AssetId>,
+    asset_names: &'a [String],
+    asset_decimals: &'a [u8],
+    asset_scaled: &'a [bool],
+    aggregates: Option<&'a Aggregates>,
+    asset_scope: AssetScope,
+    time_unit: TimeUnit,
+    attributes: &'a [AttributeColumn],
+    registry: &'a NodeRegistry,
+}
+
+// Writes the sections in their order, the single definition of the layout.
+fn write_parts<W: Write>(writer: W, parts: &SnapshotParts) -> anyhow::Result<()> {
+    let mut w = SnapshotWriter::new(writer);
+    w.write_header(parts.flags, parts.node_count, parts.edge_count)?;
+
+    w.write_section(TAG_OFFSETS_OUT, parts.offsets_out)?;
+    w.write_section(TAG_OFFSETS_IN, parts.offsets_in)?;
+    w.write_data(TAG_SRCS_OUT, &parts.srcs_out)?;
+    w.write_data(TAG_AMOUNTS_OUT, &parts.amounts_out)?;
+    w.write_adjacency(
+        &parts.adj_out,
+        [TAG_DSTS, TAG_TIMESTAMPS_OUT],
+        [TAG_PACKED_OFFSETS_OUT, TAG_PACKED_DATA_OUT],
+    )?;
+    w.write_data(TAG_AMOUNTS_IN, &parts.amounts_in)?;
+    w.write_adjacency(
+        &parts.adj_in,
+        [TAG_SRCS_IN, TAG_TIMESTAMPS_IN],
+        [TAG_PACKED_OFFSETS_IN, TAG_PACKED_DATA_IN],
+    )?;
+    w.write_data(TAG_EDGE_IDS_IN, &parts.edge_ids_in)?;
+
+    w.write_data(TAG_ASSETS, &parts.assets)?;
+    w.write_strings(
+        TAG_ASSET_NAME_OFFSETS,
+        TAG_ASSET_NAME_DATA,
+        parts.asset_names.iter().map(|a| a.as_str()),
+    )?;
+    w.write_section(TAG_ASSET_DECIMALS, parts.asset_decimals)?;
+    let scaled = parts
+        .asset_scaled
+        .iter()
+        .map(|s| *s as u8)
+        .collect::<Vec<u8>>();
+    w.write_section(TAG_ASSET_SCALED, &scaled)?;
+    if let Some(aggregates) = parts.aggregates {
+        w.write_section(TAG_LAST_TIMESTAMPS, &aggregates.last_timestamps)?;
+        w.write_section(TAG_TRANSFER_OFFSETS, &aggregates.transfer_offsets)?;
+        w.write_section(TAG_TRANSFER_AMOUNTS, &aggregates.transfer_amounts)?;
+        w.write_section(TAG_TRANSFER_TIMESTAMPS, &aggregates.transfer_timestamps)?;
+    }
+    w.write_asset_scope(parts.asset_scope)?;
+    w.write_time_unit(parts.time_unit)?;
+
+    let kinds = parts
+        .attributes
+        .iter()
+        .map(|a| kind_to_u8(a.kind))
+        .collect::<Vec<u8>>();
+    w.write_section(TAG_ATTRIBUTE_KINDS, &kinds)?;
+    for (a, attr) in parts.attributes.iter().enumerate() {
+        w.write_section(TAG_ATTRIBUTE_NAME, attr.name.as_bytes())?;
+        w.write_section(TAG_ATTRIBUTE_VALUES, &attr.values)?;
+        w.write_section(TAG_ATTRIBUTE_STR_OFFSETS, &attr.str_offsets)?;
+        w.write_section(TAG_ATTRIBUTE_STR_DATA, &attr.str_data)?;
+        if let Some(aggregates) = parts.aggregates {
+            w.write_section(
+                TAG_TRANSFER_ATTRIBUTE_VALUES,
+                &aggregates.transfer_attributes[a],
+            )?;
+        }
+    }
+
+    w.write_section(TAG_REGISTRY_OFFSETS, &parts.registry.offsets)?;
+    w.write_section(TAG_REGISTRY_DATA, &parts.registry.arena)?;
+
+    w.finish()
+}
+
+pub fn read_snapshot<R: Read>(reader: R) -> anyhow::Result<(Graph, NodeRegistry)> {
+    decode(StreamSource {
+        inner: reader,
+        hasher: crc32fast::Hasher::new(),
+        pos: 0,
+        size: None,
+    })
+}
+
+fn decode<S: Source>(mut src: S) -> anyhow::Result<(Graph, NodeRegistry)> {
+    let mut magic = [0u8; 8];
+    src.read_exact(&mut magic)?;
+    ensure!(&magic == MAGIC, "not a traceloc snapshot");
+    let [version, marker] = src.read_pod::<u32, 2>()?;
+    if marker == ENDIAN_MARKER.swap_bytes() {
+        bail!("snapshot was written with a different endianness than this machine");
+    }
+    ensure!(marker == ENDIAN_MARKER, "snapshot header is corrupted");
+    ensure!(
+        version == VERSION,
+        "unsupported snapshot version {version}, expected {VERSION}"
+    );
+    let [flags, node_count, edge_count] = src.read_pod::<u64, 3>()?;
+    src.read_padding()?;
+    ensure!(
+        node_count <= NodeId::MAX as u64 + 1 && edge_count < u64::MAX,
+        "snapshot header is corrupted"
+    );
+    let node_count = node_count as usize;
+    let edge_count = edge_count as usize;
+    let compressed = flags & FLAG_COMPRESSED != 0;
+
+    let mut graph = Graph {
+        node_count,
+        time_sorted: flags & FLAG_TIME_SORTED != 0,
+        offsets_out: src.read_section(TAG_OFFSETS_OUT, node_count + 1)?,
+        offsets_in: src.read_section(TAG_OFFSETS_IN, node_count + 1)?,
+        srcs_out: src.read_section(TAG_SRCS_OUT, edge_count)?,
+        amounts_out: src.read_section(TAG_AMOUNTS_OUT, edge_count)?,
+        adj_out: if compressed {
+            src.read_packed([TAG_PACKED_OFFSETS_OUT, TAG_PACKED_DATA_OUT], node_count)?
+        } else {
+            src.read_plain([TAG_DSTS, TAG_TIMESTAMPS_OUT], edge_count)?
+        },
+        amounts_in: src.read_section(TAG_AMOUNTS_IN, edge_count)?,
+        adj_in: if compressed {
+            src.read_packed([TAG_PACKED_OFFSETS_IN, TAG_PACKED_DATA_IN], node_count)?
+        } else {
+            src.read_plain([TAG_SRCS_IN, TAG_TIMESTAMPS_IN], edge_count)?
+        },
+        edge_ids_in: src.read_section(TAG_EDGE_IDS_IN, edge_count)?,
+        aggregates: None,
+        assets: src.read_section(TAG_ASSETS, edge_count)?,
+        asset_names: src.read_strings(TAG_ASSET_NAME_OFFSETS, TAG_ASSET_NAME_DATA)?,
+        asset_decimals: vec![],
+        asset_scaled: vec![],
+        asset_scope: AssetScope::Mixed,
+        time_unit: TimeUnit::Seconds,
+        attributes: vec![],
+    };
+    for offsets in [&graph.offsets_out, &graph.offsets_in] {
+        ensure!(
+            valid_offsets(offsets.iter().map(|o| *o as u64), edge_count),
+            "snapshot edge offsets are corrupted"
+        );
+    }
+    graph.asset_decimals = src
+        .read_section::<u8>(TAG_ASSET_DECIMALS, graph.asset_names.len())?
+        .to_vec();
+    let scaled: Column<u8> = src.read_section(TAG_ASSET_SCALED, graph.asset_names.len())?;
+    ensure!(
+        scaled.iter().all(|s| *s <= 1),
+        "snapshot asset scales are corrupted"
+    );
+    graph.asset_scaled = scaled.iter().map(|s| *s == 1).collect();
+    if flags & FLAG_AGGREGATED != 0 {
+        let aggregates = Aggregates {
+            last_timestamps: src.read_section(TAG_LAST_TIMESTAMPS, edge_count)?,
+            transfer_offsets: src.read_section(TAG_TRANSFER_OFFSETS, edge_count + 1)?,
+            transfer_amounts: src.read_section(TAG_TRANSFER_AMOUNTS, None)?,
+            transfer_timestamps: src.read_section(TAG_TRANSFER_TIMESTAMPS, None)?,
+            transfer_attributes: vec![],
+        };
+        graph.aggregates = Some(aggregates);
+    }
+    let scope: Column<u32> = src.read_section(TAG_ASSET_SCOPE, 2)?;
+    graph.asset_scope = match scope[0] {
+        SCOPE_SINGLE => AssetScope::Single(scope[1] as AssetId),
+        SCOPE_MIXED => AssetScope::Mixed,
+        SCOPE_NORMALIZED => AssetScope::Normalized,
+        other => bail!("unknown asset scope {other}"),
+    };
+    let unit: Column<u32> = src.read_section(TAG_TIME_UNIT, 2)?;
+    graph.time_unit = match unit[0] {
+        UNIT_SECONDS => TimeUnit::Seconds,
+        UNIT_MILLISECONDS => TimeUnit::Milliseconds,
+        UNIT_BLOCKS => TimeUnit::Blocks {
+            block_secs: unit[1],
+        },
+        other => bail!("unknown time unit {other}"),
+    };
+
+    let kinds: Column<u8> = src.read_section(TAG_ATTRIBUTE_KINDS, None)?;
+    for kind in kinds.iter() {
+        let name: Column<u8> = src.read_section(TAG_ATTRIBUTE_NAME, None)?;
+        let attr = AttributeColumn {
+            name: String::from_utf8(name.to_vec())?,
+            kind: kind_from_u8(*kind)?,
+            values: src.read_section(TAG_ATTRIBUTE_VALUES, edge_count)?,
+            str_offsets: src.read_section(TAG_ATTRIBUTE_STR_OFFSETS, None)?,
+            str_data: src.read_section(TAG_ATTRIBUTE_STR_DATA, None)?,
+        };
+        attr.validate(edge_count)?;
+        graph.attributes.push(attr);
+        if let Some(aggregates) = &mut graph.aggregates {
+            let values = src.read_section(TAG_TRANSFER_ATTRIBUTE_VALUES, None)?;
+            aggregates.transfer_attributes.push(values);
+        }
+    }
+    if let Some(aggregates) = &graph.aggregates {
+        aggregates.validate(edge_count, &graph.attributes)?;
+    }
+    validate_ids(&graph)?;
+
+    let offsets: Column<u64> = src.read_section(TAG_REGISTRY_OFFSETS, None)?;
+    let arena: Column<u8> = src.read_section(TAG_REGISTRY_DATA, None)?;
+    let registry = NodeRegistry::from_arena(offsets.to_vec(), arena.to_vec())?;
+
+    src.finish()?;
+
+    Ok((graph, registry))
+}
+
+// Checks that the node, edge and asset ids in the edge arrays are in range, so that a corrupted
+// snapshot fails to load instead of panicking in a query. The offsets are already checked.
+fn validate_ids(graph: &Graph) -> anyhow::Result<()> {
+    let node_count = graph.node_count;
+    ensure!(
+        graph.offsets_out.windows(2).enumerate().all(|(n, range)| {
+            graph.srcs_out[range[0]..range[1]]
+                .iter()
+                .all(|src| *src as usize == n)
+        }),
+        "snapshot edge sources are corrupted"
+    );
+    ensure!(
+        graph.adj_out.valid_nodes(&graph.offsets_out, node_count)
+            && graph.adj_in.valid_nodes(&graph.offsets_in, node_count),
+        "snapshot neighbour ids are corrupted"
+    );
+    let edge_count = graph.edge_ids_in.len() as EdgeId;
+    ensure!(
+        graph.edge_ids_in.iter().all(|e| *e < edge_count),
+        "snapshot edge ids are corrupted"
+    );
+    let asset_count = graph.asset_names.len();
+    ensure!(
+        graph.assets.iter().all(|a| (*a as usize) < asset_count),
+        "snapshot edge assets are corrupted"
+    );
+    Ok(())
+}
+
+struct SnapshotWriter<W: Write> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+    written: usize,
+}
+
+impl<W: Write> SnapshotWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            written: 0,
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> anyhow::Result<()> {
+        self.inner.write_all(buf)?;
+        self.hasher.update(buf);
+        self.written += buf.len();
+        Ok(())
+    }
+
+    fn write_pod<T: Pod>(&mut self, values: &[T]) -> anyhow::Result<()> {
+        self.write_all(bytemuck::cast_slice(values))
+    }
+
+    fn write_header(
+        &mut self,
+        flags: u64,
+        node_count: usize,
+        edge_count: usize,
+    ) -> anyhow::Result<()> {
+        self.write_all(MAGIC)?;
+        self.write_pod(&[VERSION, ENDIAN_MARKER])?;
+        self.write_pod(&[flags, node_count as u64, edge_count as u64])?;
+        self.write_padding()
+    }
+
+    fn write_section<T: Pod>(&mut self, tag: u32, values: &[T]) -> anyhow::Result<()> {
+        self.write_pod(&[tag, size_of::<T>() as u32])?;
+        self.write_pod(&[values.len() as u64])?;
+        self.write_pod(values)?;
+        self.write_padding()
+    }
+
+    // Streams a section from a file holding its raw elements.
+    fn copy_section<T: Pod>(&mut self, tag: u32, path: &Path) -> anyhow::Result<()> {
+        let mut file = BufReader::new(File::open(path)?);
+        let len = file.get_ref().metadata()?.len() as usize;
+        ensure!(
+            len.is_multiple_of(size_of::<T>()),
+            "column file {} is truncated",
+            path.display()
+        );
+        self.write_pod(&[tag, size_of::<T>() as u32])?;
+        self.write_pod(&[(len / size_of::<T>()) as u64])?;
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            self.write_all(&buf[..n])?;
+        }
+        self.write_padding()
+    }
+
+    fn write_padding(&mut self) -> anyhow::Result<()> {
+        let padding = self.written.next_multiple_of(ALIGN) - self.written;
+        self.write_all(&[0; ALIGN][..padding])
+    }
+
+    fn write_asset_scope(&mut self, scope: AssetScope) -> anyhow::Result<()> {
+        let scope = match scope {
+            AssetScope::Single(asset) => [SCOPE_SINGLE, asset as u32],
+            AssetScope::Mixed => [SCOPE_MIXED, 0],
+            AssetScope::Normalized => [SCOPE_NORMALIZED, 0],
+        };
+        self.write_section(TAG_ASSET_SCOPE, &scope)
+    }
+
+    fn write_time_unit(&mut self, unit: TimeUnit) -> anyhow::Result<()> {
+        let unit = match unit {
+            TimeUnit::Seconds => [UNIT_SECONDS, 0],
+            TimeUnit::Milliseconds => [UNIT_MILLISECONDS, 0],
+            TimeUnit::Blocks { block_secs } => [UNIT_BLOCKS, block_secs],
+        };
+        self.write_section(TAG_TIME_UNIT, &unit)
+    }
+
+    fn write_data<T: Pod>(&mut self, tag: u32, data: &SectionData<T>) -> anyhow::Result<()> {
+        match data {
+            SectionData::Memory(values) => self.write_section(tag, values),
+            SectionData::File(path) => self.copy_section::<T>(tag, path),
+        }
+    }
+
+    fn write_adjacency(
+        &mut self,
+        adj: &AdjacencyData,
+        plain_tags: [u32; 2],
+        packed_tags: [u32; 2],
+    ) -> anyhow::Result<()> {
+        match adj {
+            AdjacencyData::Memory(Adjacency::Plain { nodes, timestamps }) => {
+                self.write_section(plain_tags[0], nodes)?;
+                self.write_section(plain_tags[1], timestamps)
+            }
+            AdjacencyData::Memory(Adjacency::Compressed { offsets, data }) => {
+                self.write_section(packed_tags[0], offsets)?;
+                self.write_section(packed_tags[1], data)
+            }
+            AdjacencyData::Files { nodes, timestamps } => {
+                self.copy_section::<NodeId>(plain_tags[0], nodes)?;
+                self.copy_section::<u64>(plain_tags[1], timestamps)
+            }
+        }
+    }
+
+    fn write_strings<'a>(
+        &mut self,
+        offsets_tag: u32,
+        data_tag: u32,
+        strings: impl Iterator<Item = &'a str>,
+    ) -> anyhow::Result<()> {
+        let mut offsets = vec![0u64];
+        let mut data = Vec::new();
+        for s in strings {
+            data.extend_from_slice(s.as_bytes());
+            offsets.push(data.len() as u64);
+        }
+        self.write_section(offsets_tag, &offsets)?;
+        self.write_section(data_tag, &data)
+    }
+
+    fn finish(mut self) -> anyhow::Result<()> {
+        let checksum = self.hasher.clone().finalize();
+        self.inner.write_all(&checksum.to_ne_bytes())?;
+        Ok(())
+    }
+}
+
+trait Source {
+    fn position(&self) -> usize;
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> anyhow::Result<()>;
+
+    fn read_column<T: Pod>(&mut self, len: usize) -> anyhow::Result<Column<T>>;
+
+    fn finish(self) -> anyhow::Result<()>;
+
+    fn read_pod<T: Pod, const N: usize>(&mut self) -> anyhow::Result<[T; N]> {
+        let mut values = [T::zeroed(); N];
+        self.read_exact(bytemuck::cast_slice_mut(&mut values))?;
+        Ok(values)
+    }
+
+    fn read_section<T: Pod>(
+        &mut self,
+        tag: u32,
+        expected_len: impl Into<Option<usize>>,
+    ) -> anyhow::Result<Column<T>> {
+        let [actual_tag, elem_size] = self.read_pod::<u32, 2>()?;
+        let [len] = self.read_pod::<u64, 1>()?;
+        ensure!(
+            actual_tag == tag,
+            "unexpected snapshot section {actual_tag}, expected {tag}"
+        );
+        ensure!(
+            elem_size as usize == size_of::<T>(),
+            "snapshot section {tag} has {elem_size} byte elements, expected {}",
+            size_of::<T>()
+        );
+        if let Some(expected_len) = expected_len.into() {
+            ensure!(
+                len as usize == expected_len,
+                "snapshot section {tag} has {len} elements, expected {expected_len}"
+            );
+        }
+
+        let values = self.read_column(len as usize)?;
+        self.read_padding()?;
+        Ok(values)
+    }
+
+    fn read_padding(&mut self) -> anyhow::Result<()> {
+        let padding = self.position().next_multiple_of(ALIGN) - self.position();
+        self.read_exact(&mut [0; ALIGN][..padding])
+    }
+
+    fn read_plain(&mut self, tags: [u32; 2], edge_count: usize) -> anyhow::Result<Adjacency> {
+        Ok(Adjacency::Plain {
+            nodes: self.read_section(tags[0], edge_count)?,
+            timestamps: self.read_section(tags[1], edge_count)?,
+        })
+    }
+
+    fn read_packed(&mut self, tags: [u32; 2], node_count: usize) -> anyhow::Result<Adjacency> {
+        let adj = Adjacency::Compressed {
+            offsets: self.read_section(tags[0], node_count + 1)?,
+            data: self.read_section(tags[1], None)?,
+        };
+        adj.validate(node_count)?;
+        Ok(adj)
+    }
+
+    fn read_strings(&mut self, offsets_tag: u32, data_tag: u32) -> anyhow::Result<Vec<String>> {
+        let offsets: Column<u64> = self.read_section(offsets_tag, None)?;
+        let data: Column<u8> = self.read_section(data_tag, None)?;
+        ensure!(
+            !offsets.is_empty(),
+            "snapshot section {offsets_tag} is corrupted"
+        );
+        let mut strings = Vec::with_capacity(offsets.len() - 1);
+        for w in offsets.windows(2) {
+            let bytes = data
+                .get(w[0] as usize..w[1] as usize)
+                .ok_or_else(|| anyhow::anyhow!("snapshot section {data_tag} is corrupted"))?;
+            strings.push(String::from_utf8(bytes.to_vec())?);
+        }
+        Ok(strings)
+    }
+}
+
+struct StreamSource<R: Read> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    pos: usize,
+    // bytes of the snapshot, if known
+    size: Option<usize>,
+}
+
+impl<R: Read> Source for StreamSource<R> {
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> anyhow::Result<()> {
+        self.inner.read_exact(buf)?;
+        self.hasher.update(buf);
+        self.pos += buf.len();
+        Ok(())
+    }
+
+    // The length comes from the file before its checksum is verified, so it is checked against
+    // the size of the file where known, and otherwise only backed by memory as data arrives.
+    fn read_column<T: Pod>(&mut self, len: usize) -> anyhow::Result<Column<T>> {
+        let bytes = len.checked_mul(size_of::<T>());
+        let remaining = self.size.map(|size| size.saturating_sub(self.pos));
+        ensure!(
+            bytes.is_some_and(|bytes| remaining.is_none_or(|remaining| bytes <= remaining)),
+            "snapshot section of {len} elements exceeds the file"
+        );
+        let mut values = Vec::new();
+        while values.len() < len {
+            let from = values.len();
+            let chunk = (len - from).min(READ_CHUNK / size_of::<T>().max(1));
+            values.resize(from + chunk, T::zeroed());
+            self.read_exact(bytemuck::cast_slice_mut(&mut values[from..]))?;
+        }
+        Ok(values.into())
+    }
+
+    fn finish(mut self) -> anyhow::Result<()> {
+        let expected = self.hasher.clone().finalize();
+        let mut checksum = [0; 4];
+        self.inner.read_exact(&mut checksum)?;
+        ensure!(
+            u32::from_ne_bytes(checksum) == expected,
+            "snapshot checksum mismatch, the file is corrupted"
+        );
+        Ok(())
+    }
+}
+
+struct MappedSource {
+    map: Arc<Mmap>,
+    pos: usize,
+}
+
+impl Source for MappedSource {
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> anyhow::Result<()> {
+        let bytes = self
+            .map
+            .get(self.pos..self.pos + buf.len())
+            .ok_or_else(|| anyhow::anyhow!("snapshot is truncated"))?;
+        buf.copy_from_slice(bytes);
+        self.pos += buf.len();
+        Ok(())
+    }
+
+    fn read_column<T: Pod>(&mut self, len: usize) -> anyhow::Result<Column<T>> {
+        let slice = MappedSlice::new(self.map.clone(), self.pos, len)
+            .ok_or_else(|| anyhow::anyhow!("snapshot is truncated or misaligned"))?;
+        self.pos += len * size_of::<T>();
+        Ok(Column::Mapped(slice))
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        ensure!(self.map.len() == self.pos + 4, "snapshot is truncated");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::attributes::{AttributeKind, AttributeValue};
+    use crate::core::graph::{FreezeConfig, GraphBuilder};
+    use crate::core::ids::DEFAULT_ASSET;
+
+    fn sample() -> (Graph, NodeRegistry) {
+        let mut registry = NodeRegistry::new();
+        let a = registry.get_or_insert("a");
+        let b = registry.get_or_insert("b");
+        let c = registry.get_or_insert("c");
+        let mut gb = GraphBuilder::new(registry.len());
+        let eth = gb.asset_id("ETH").unwrap();
+        gb.set_asset_decimals(eth, 18).unwrap();
+        gb.set_time_unit(TimeUnit::Blocks { block_secs: 12 });
+        gb.add_edge(a, c, 7, 8).unwrap();
+        gb.add_edge(c, a, 1, 2).unwrap();
+        gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+        gb.add_attribute("fee", AttributeKind::U64).unwrap();
+        gb.add_edge_with_attributes(
+            a,
+            b,
+            3,
+            4,
+            &[AttributeValue::Str("0xab"), AttributeValue::U64(1)],
+        )
+        .unwrap();
+        gb.add_asset_edge(
+            b,
+            c,
+            eth,
+            5 * 10u128.pow(30),
+            6,
+            &[AttributeValue::Str(""), AttributeValue::U64(0)],
+        )
+        .unwrap();
+        (gb.freeze(), registry)
+    }
+
+    fn assert_same_graph(expected: &Graph, actual: &Graph) {
+        assert_eq!(expected.node_count(), actual.node_count());
+        assert_eq!(expected.edge_count(), actual.edge_count());
+        assert_eq!(expected.asset_scope(), actual.asset_scope());
+        assert_eq!(expected.time_unit(), actual.time_unit());
+        assert_eq!(expected.asset_names, actual.asset_names);
+        assert_eq!(expected.asset_decimals, actual.asset_decimals);
+        assert_eq!(expected.asset_scaled, actual.asset_scaled);
+        assert_eq!(expected.assets, actual.assets);
+        assert_eq!(expected.offsets_out, actual.offsets_out);
+        assert_eq!(expected.offsets_in, actual.offsets_in);
+        for n in 0..expected.node_count() as u32 {
+            assert!(expected.edges_from(n).eq(actual.edges_from(n)));
+            assert!(expected.edges_to(n).eq(actual.edges_to(n)));
+        }
+        assert_eq!(expected.attributes().len(), actual.attributes().len());
+        for (attr, column) in expected.attributes().iter().enumerate() {
+            assert_eq!(column.name(), actual.attributes()[attr].name());
+            for e in 0..expected.edge_count() as u64 {
+                assert_eq!(expected.attribute(attr, e), actual.attribute(attr, e));
+            }
+        }
+    }
+
+    fn to_bytes(graph: &Graph, registry: &NodeRegistry) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_snapshot(&mut buf, graph, registry).unwrap();
+        buf
+    }
+
+    // Position of the first element of the first section with `tag`.
+    fn section_data(bytes: &[u8], tag: u32) -> usize {
+        let mut pos = 48;
+        loop {
+            let header = bytemuck::pod_read_unaligned::<[u32; 4]>(&bytes[pos..pos + 16]);
+            let len = bytemuck::pod_read_unaligned::<u64>(&bytes[pos + 8..pos + 16]) as usize;
+            if header[0] == tag {
+                return pos + 16;
+            }
+            pos = (pos + 16 + header[1] as usize * len).next_multiple_of(ALIGN);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (g, registry) = sample();
+        let (loaded, loaded_registry) = read_snapshot(&to_bytes(&g, &registry)[..]).unwrap();
+
+        assert!(!loaded.is_time_sorted());
+        assert_same_graph(&g, &loaded);
+        let tx = loaded.attribute_id("tx_hash").unwrap();
+        let e = loaded.edges_from(0).find(|e| e.dst == 1).unwrap();
+        assert_eq!(AttributeValue::Str("0xab"), loaded.attribute(tx, e.id));
+        assert_eq!(3, loaded_registry.len());
+        assert_eq!(Some(0), loaded_registry.get("a"));
+        assert_eq!(Some(1), loaded_registry.get("b"));
+        assert_eq!(Some(2), loaded_registry.get("c"));
+    }
+
+    #[test]
+    fn test_unscaled_asset_round_trip() {
+        let mut gb = GraphBuilder::new(2);
+        let eth = gb.asset_id("ETH").unwrap();
+        let usdt = gb.asset_id("USDT").unwrap();
+        gb.set_asset_decimals(eth, 18).unwrap();
+        gb.add_asset_edge(0, 1, usdt, 5, 1, &[]).unwrap();
+        let g = gb.freeze();
+        let path = std::env::temp_dir().join(format!("traceloc-scaled-{}", std::process::id()));
+        save_snapshot(&path, &g, &NodeRegistry::new()).unwrap();
+        let (mapped, _) = map_snapshot(&path).unwrap();
+        let (loaded, _) = load_snapshot(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        for graph in [&mapped, &loaded] {
+            let table = graph.asset_table();
+            assert_eq!(None, table.scale(DEFAULT_ASSET));
+            assert_eq!((Some(18), None), (table.scale(eth), table.scale(usdt)));
+        }
+    }
+
+    #[test]
+    fn test_time_sorted_flag() {
+        let mut gb = GraphBuilder::new(2);
+        gb.add_edge(0, 1, 1, 2).unwrap();
+        let g = gb.freeze_with(&FreezeConfig {
+            sort_by_time: true,
+            ..Default::default()
+        });
+        let registry = NodeRegistry::new();
+        let (loaded, _) = read_snapshot(&to_bytes(&g, &registry)[..]).unwrap();
+
+        assert!(loaded.is_time_sorted());
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let mut gb = GraphBuilder::new(4);
+        for i in 0..20u32 {
+            gb.add_edge(
+                i % 4,
+                (i * 7) % 4,
+                i as Amount,
+                1_700_000_000 + (i as u64 * 37) % 11,
+            )
+            .unwrap();
+        }
+        let g = gb.freeze_with(&FreezeConfig {
+            compress: true,
+            ..Default::default()
+        });
+        let registry = NodeRegistry::new();
+        let path = std::env::temp_dir().join(format!("traceloc-packed-{}", std::process::id()));
+        save_snapshot(&path, &g, &registry).unwrap();
+
+        let (loaded, _) = load_snapshot(&path).unwrap();
+        let (mapped, _) = map_snapshot(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(loaded.is_compressed());
+        assert!(mapped.is_compressed() && mapped.is_mapped());
+        assert_same_graph(&g, &loaded);
+        assert_same_graph(&g, &mapped);
+    }
+
+    #[test]
+    fn test_aggregated_round_trip() {
+        let mut gb = GraphBuilder::new(2);
+        gb.add_attribute("tx_hash", AttributeKind::Str).unwrap();
+        for (src, dst, amount, ts, tx) in [
+            (0, 1, 1, 5, "0x1"),
+            (1, 0, 2, 6, "0x2"),
+            (0, 1, 3, 7, "0x3"),
+        ] {
+            gb.add_edge_with_attributes(src, dst, amount, ts, &[AttributeValue::Str(tx)])
+                .unwrap();
+        }
+        let g = gb
+            .try_freeze_with(&FreezeConfig {
+                aggregate_parallel: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let (loaded, _) = read_snapshot(&to_bytes(&g, &NodeRegistry::new())[..]).unwrap();
+
+        assert_same_graph(&g, &loaded);
+        assert_eq!(g.aggregate(0), loaded.aggregate(0));
+        let transfers = loaded.transfers(0).unwrap().collect::<Vec<_>>();
+        assert_eq!(AttributeValue::Str("0x3"), transfers[1].attributes[0]);
+        assert!(g.transfers(0).unwrap().eq(transfers));
+
+        let mut bytes = to_bytes(&g, &NodeRegistry::new());
+        let value = section_data(&bytes, TAG_TRANSFER_ATTRIBUTE_VALUES);
+        bytes[value..value + 8].copy_from_slice(&9u64.to_ne_bytes());
+        let error = read_snapshot(&bytes[..]).err().unwrap().to_string();
+        assert_eq!("aggregated transfers are corrupted", error);
+    }
+
+    #[test]
+    fn test_empty_graph() {
+        let g = GraphBuilder::new(0).freeze();
+        let registry = NodeRegistry::new();
+        let (loaded, loaded_registry) = read_snapshot(&to_bytes(&g, &registry)[..]).unwrap();
+
+        assert_eq!(0, loaded.node_count());
+        assert_eq!(0, loaded.edge_count());
+        assert!(loaded_registry.is_empty());
+    }
+
+    #[test]
+    fn test_sections_aligned() {
+        let (g, registry) = sample();
+        let bytes = to_bytes(&g, &registry);
+
+        assert_eq!(0, (bytes.len() - 4) % ALIGN);
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let (g, registry) = sample();
+        let mut bytes = to_bytes(&g, &registry);
+        bytes[0] = b'X';
+
+        let err = read_snapshot(&bytes[..]).err().unwrap();
+        assert!(err.to_string().contains("not a traceloc snapshot"));
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let (g, registry) = sample();
+        let mut bytes = to_bytes(&g, &registry);
+        bytes[8..12].copy_from_slice(&(VERSION + 1).to_ne_bytes());
+
+        let err = read_snapshot(&bytes[..]).err().unwrap();
+        assert!(err.to_string().contains("unsupported snapshot version"));
+    }
+
+    #[test]
+    fn test_endianness_mismatch() {
+        let (g, registry) = sample();
+        let mut bytes = to_bytes(&g, &registry);
+        bytes[12..16].copy_from_slice(&ENDIAN_MARKER.swap_bytes().to_ne_bytes());
+
+        let err = read_snapshot(&bytes[..]).err().unwrap();
+        assert!(err.to_string().contains("endianness"));
+    }
+
+    #[test]
+    fn test_corrupted_payload() {
+        let (g, registry) = sample();
+        let mut bytes = to_bytes(&g, &registry);
+        let last = bytes.len() - 5;
+        bytes[last] ^= 0xff;
+
+        let err = read_snapshot(&bytes[..]).err().unwrap();
+        assert!(err.to_string().contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn test_map_snapshot() {
+        let (g, registry) = sample();
+        let path = std::env::temp_dir().join(format!("traceloc-map-{}", std::process::id()));
+        save_snapshot(&path, &g, &registry).unwrap();
+
+        verify_snapshot(&path).unwrap();
+        let (mapped, mapped_registry) = map_snapshot(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(mapped.is_mapped());
+        assert!(!g.is_mapped());
+        assert_same_graph(&g, &mapped);
+        assert_eq!(Some(2), mapped_registry.get("c"));
+    }
+
+    #[test]
+    fn test_save_over_mapped_snapshot() {
+        let (g, registry) = sample();
+        let dir = std::env::temp_dir().join(format!("traceloc-resave-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("graph.snapshot");
+        save_snapshot(&path, &g, &registry).unwrap();
+        let (mapped, _) = map_snapshot(&path).unwrap();
+
+        let empty = GraphBuilder::new(0).freeze();
+        save_snapshot(&path, &empty, &NodeRegistry::new()).unwrap();
+        assert_same_graph(&g, &mapped);
+        assert_eq!(0, map_snapshot(&path).unwrap().0.node_count());
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(1, files);
+    }
+
+    #[test]
+    fn test_verify_corrupted() {
+        let (g, registry) = sample();
+        let path = std::env::temp_dir().join(format!("traceloc-verify-{}", std::process::id()));
+        let mut bytes = to_bytes(&g, &registry);
+        bytes[40] ^= 0xff;
+        std::fs::write(&path, bytes).unwrap();
+
+        let result = verify_snapshot(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_oversized_section() {
+        let (g, registry) = sample();
+        let mut bytes = to_bytes(&g, &registry);
+        // header edge count and the element count of the `srcs_out` section behind the two
+        // offset sections of the 3 node graph
+        assert_eq!(TAG_SRCS_OUT.to_ne_bytes(), bytes[144..148]);
+        for count in [1u64 << 60, u64::MAX / 2] {
+            bytes[32..40].copy_from_slice(&count.to_ne_bytes());
+            bytes[152..160].copy_from_slice(&count.to_ne_bytes());
+            assert!(read_snapshot(&bytes[..]).is_err());
+
+            let path = std::env::temp_dir().join(format!("traceloc-huge-{}", std::process::id()));
+            std::fs::write(&path, &bytes).unwrap();
+            let result = load_snapshot(&path);
+            std::fs::remove_file(&path).unwrap();
+            let error = result.err().unwrap().to_string();
+            assert!(error.contains("exceeds the file"), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_map_corrupted_offsets() {
+        let (g, registry) = sample();
+        let mut bytes = to_bytes(&g, &registry);
+        // the second entry of `offsets_out`, right behind the header and the section header
+        assert_eq!(TAG_OFFSETS_OUT.to_ne_bytes(), bytes[48..52]);
+        bytes[72..80].copy_from_slice(&100usize.to_ne_bytes());
+        let path = std::env::temp_dir().join(format!("traceloc-offsets-{}", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let result = map_snapshot(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let error = result.err().unwrap().to_string();
+        assert!(error.contains("edge offsets are corrupted"), "{error}");
+    }
+
+    #[test]
+    fn test_map_corrupted_ids() {
+        let (g, registry) = sample();
+        let bytes = to_bytes(&g, &registry);
+        let path = std::env::temp_dir().join(format!("traceloc-ids-{}", std::process::id()));
+        // the first edge is `a -> c`, `a -> b` is the last one into `b`
+        for (tag, value, message) in [
+            (TAG_SRCS_OUT, &1u32.to_ne_bytes()[..], "edge sources"),
+            (TAG_DSTS, &3u32.to_ne_bytes(), "neighbour ids"),
+            (TAG_SRCS_IN, &3u32.to_ne_bytes(), "neighbour ids"),
+            (TAG_EDGE_IDS_IN, &4u64.to_ne_bytes(), "edge ids"),
+            (TAG_ASSETS, &2u16.to_ne_bytes(), "edge assets"),
+        ] {
+            let mut corrupted = bytes.clone();
+            let data = section_data(&bytes, tag);
+            corrupted[data..data + value.len()].copy_from_slice(value);
+            std::fs::write(&path, &corrupted).unwrap();
+            let error = map_snapshot(&path).err().unwrap().to_string();
+            assert_eq!(format!("snapshot {message} are corrupted"), error);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        let mut gb = GraphBuilder::new(3);
+        gb.add_edge(0, 2, 1, 1).unwrap();
+        let g = gb.freeze_with(&FreezeConfig {
+            compress: true,
+            ..Default::default()
+        });
+        let mut bytes = to_bytes(&g, &registry);
+        let data = section_data(&bytes, TAG_PACKED_DATA_OUT);
+        // a neighbour 50 nodes after node 0, and a neighbour varint running into the timestamp
+        for value in [100, 0x80] {
+            bytes[data] = value;
+            let error = read_snapshot(&bytes[..]).err().unwrap().to_string();
+            assert_eq!("snapshot neighbour ids are corrupted", error);
+        }
+    }
+
+    #[test]
+    fn test_corrupted_attribute_strings() {
+        let (g, registry) = sample();
+        let bytes = to_bytes(&g, &registry);
+        let data = bytes
+            .windows(4)
+            .position(|w| w == b"0xab")
+            .expect("tx hash in the string table");
+        let mut invalid = bytes.clone();
+        invalid[data] = 0xff;
+        let path = std::env::temp_dir().join(format!("traceloc-strings-{}", std::process::id()));
+        std::fs::write(&path, &invalid).unwrap();
+        let result = map_snapshot(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let error = result.err().unwrap().to_string();
+        assert!(
+            error.contains("tx_hash string table is corrupted"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let (g, registry) = sample();
+        let bytes = to_bytes(&g, &registry);
+
+        assert!(read_snapshot(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
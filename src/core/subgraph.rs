@@ -0,0 +1,212 @@
+use crate::core::graph::Graph;
+use crate::core::ids::{NodeId, NodeRegistry};
+use anyhow::ensure;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+    Both,
+}
+
+/// Standalone graph extracted from a parent graph, with nodes renumbered densely in the order
+/// of their parent ids. The graph keeps the assets and attributes of the parent but not its
+/// aggregates: an aggregated edge becomes a plain edge with the total amount and the attribute
+/// values of its first transfer.
+pub struct Subgraph {
+    pub graph: Graph,
+    /// Parent id of every node, indexed by its id in `graph`.
+    pub parent_ids: Vec<NodeId>,
+}
+
+impl Subgraph {
+    pub fn parent_id(&self, node: NodeId) -> NodeId {
+        self.parent_ids[node as usize]
+    }
+
+    pub fn local_id(&self, parent: NodeId) -> Option<NodeId> {
+        self.parent_ids
+            .binary_search(&parent)
+            .ok()
+            .map(|n| n as NodeId)
+    }
+
+    /// Registry mapping the external ids of the parent's nodes to their ids in the subgraph;
+    /// `parent` must know every parent id.
+    pub fn registry(&self, parent: &NodeRegistry) -> anyhow::Result<NodeRegistry> {
+        if let Some(last) = self.parent_ids.last() {
+            ensure!(
+                (*last as usize) < parent.len(),
+                "node {last} is not in the parent registry of {} nodes",
+                parent.len()
+            );
+        }
+        Ok(NodeRegistry::from_external_ids(
+            self.parent_ids.iter().map(|p| parent.external_id(*p)),
+        ))
+    }
+}
+
+impl Graph {
+    /// The given nodes and all edges between them.
+    pub fn induced_subgraph(&self, nodes: &[NodeId]) -> anyhow::Result<Subgraph> {
+        self.check_nodes(nodes)?;
+        Ok(self.extract(nodes.to_vec(), None))
+    }
+
+    /// Nodes within `hops` edges of any of the seeds and the edges between them; with a window
+    /// only edges with a timestamp in `t0..=t1` are followed and kept.
+    pub fn k_hop_subgraph(
+        &self,
+        seeds: &[NodeId],
+        hops: usize,
+        direction: Direction,
+        window: Option<(u64, u64)>,
+    ) -> anyhow::Result<Subgraph> {
+        self.check_nodes(seeds)?;
+        let (t0, t1) = window.unwrap_or((0, u64::MAX));
+        let mut visited = seeds.iter().copied().collect::<HashSet<_>>();
+        let mut frontier = visited.iter().copied().collect::<Vec<_>>();
+        for _ in 0..hops {
+            let mut next = vec![];
+            for node in frontier {
+                if direction != Direction::Backward {
+                    next.extend(self.edges_from_between(node, t0, t1).map(|e| e.dst));
+                }
+                if direction != Direction::Forward {
+                    next.extend(self.edges_to_between(node, t0, t1).map(|e| e.src));
+                }
+            }
+            next.retain(|n| visited.insert(*n));
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        Ok(self.extract(visited.into_iter().collect(), window))
+    }
+
+    /// Edges with a timestamp in `t0..=t1` and the nodes they connect.
+    pub fn time_window_subgraph(&self, t0: u64, t1: u64) -> Subgraph {
+        let mut nodes = vec![];
+        for n in 0..self.node_count() as NodeId {
+            if self.edges_from_between(n, t0, t1).next().is_some()
+                || self.edges_to_between(n, t0, t1).next().is_some()
+            {
+                nodes.push(n);
+            }
+        }
+        self.extract(nodes, Some((t0, t1)))
+    }
+
+    fn check_nodes(&self, nodes: &[NodeId]) -> anyhow::Result<()> {
+        for node in nodes {
+            ensure!(
+                (*node as usize) < self.node_count(),
+                "node {node} is out of range of {} nodes",
+                self.node_count()
+            );
+        }
+        Ok(())
+    }
+
+    fn extract(&self, mut parent_ids: Vec<NodeId>, window: Option<(u64, u64)>) -> Subgraph {
+        parent_ids.sort_unstable();
+        parent_ids.dedup();
+        let local_ids = parent_ids
+            .iter()
+            .enumerate()
+            .map(|(local, parent)| (*parent, local as NodeId))
+            .collect::<HashMap<_, _>>();
+        let (t0, t1) = window.unwrap_or((0, u64::MAX));
+        let graph = self.rebuild_from(parent_ids.len(), parent_ids.iter().copied(), |src, e| {
+            let dst = *local_ids.get(&e.dst)?;
+            (t0..=t1)
+                .contains(&e.timestamp)
+                .then(|| (local_ids[&src], dst, e.amount))
+        });
+        Subgraph { graph, parent_ids }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::graph::GraphBuilder;
+
+    // a -> b -> c -> d, e -> b, with timestamps 10, 20, 30 and 40
+    fn sample() -> (Graph, NodeRegistry) {
+        let mut registry = NodeRegistry::new();
+        let ids = ["a", "b", "c", "d", "e"].map(|id| registry.get_or_insert(id));
+        let mut gb = GraphBuilder::new(registry.len());
+        gb.add_edge(ids[0], ids[1], 1, 10).unwrap();
+        gb.add_edge(ids[1], ids[2], 2, 20).unwrap();
+        gb.add_edge(ids[2], ids[3], 3, 30).unwrap();
+        gb.add_edge(ids[4], ids[1], 4, 40).unwrap();
+        (gb.freeze(), registry)
+    }
+
+    #[test]
+    fn test_induced_subgraph() {
+        let (g, registry) = sample();
+        let sub = g.induced_subgraph(&[3, 1, 2, 1]).unwrap();
+
+        assert_eq!(vec![1, 2, 3], sub.parent_ids);
+        assert_eq!(3, sub.graph.node_count());
+        assert_eq!(2, sub.graph.edge_count());
+        assert_eq!(Some(0), sub.local_id(1));
+        assert_eq!(None, sub.local_id(0));
+        assert_eq!(3, sub.parent_id(2));
+        let sub_registry = sub.registry(&registry).unwrap();
+        let b = sub_registry.get("b").unwrap();
+        let c = sub_registry.get("c").unwrap();
+        assert_eq!(None, sub_registry.get("a"));
+        assert_eq!(
+            vec![c],
+            sub.graph.edges_from(b).map(|e| e.dst).collect::<Vec<_>>()
+        );
+
+        assert!(g.induced_subgraph(&[1, 5]).is_err());
+        let mut short = NodeRegistry::new();
+        short.get_or_insert("a");
+        assert!(sub.registry(&short).is_err());
+    }
+
+    #[test]
+    fn test_k_hop_subgraph() {
+        let (g, _) = sample();
+
+        let forward = g.k_hop_subgraph(&[0], 2, Direction::Forward, None).unwrap();
+        assert_eq!(vec![0, 1, 2], forward.parent_ids);
+        let backward = g
+            .k_hop_subgraph(&[2], 1, Direction::Backward, None)
+            .unwrap();
+        assert_eq!(vec![1, 2], backward.parent_ids);
+        let both = g.k_hop_subgraph(&[1], 1, Direction::Both, None).unwrap();
+        assert_eq!(vec![0, 1, 2, 4], both.parent_ids);
+        assert_eq!(3, both.graph.edge_count());
+
+        let windowed = g
+            .k_hop_subgraph(&[1], 5, Direction::Both, Some((15, 35)))
+            .unwrap();
+        assert_eq!(vec![1, 2, 3], windowed.parent_ids);
+        assert_eq!(2, windowed.graph.edge_count());
+        assert!(g.k_hop_subgraph(&[7], 1, Direction::Both, None).is_err());
+    }
+
+    #[test]
+    fn test_time_window_subgraph() {
+        let (g, _) = sample();
+        let sub = g.time_window_subgraph(20, 40);
+
+        assert_eq!(vec![1, 2, 3, 4], sub.parent_ids);
+        assert_eq!(3, sub.graph.edge_count());
+        let ts = sub
+            .graph
+            .edges_to(0)
+            .map(|e| e.timestamp)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![40], ts);
+    }
+}
@@ -0,0 +1,47 @@
+/// Unit of the edge timestamps of a graph, fixed at ingest. Durations given in seconds, like
+/// `MixerConfig::window_secs`, are converted into it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeUnit {
+    /// Seconds since the Unix epoch.
+    #[default]
+    Seconds,
+    /// Milliseconds since the Unix epoch.
+    Milliseconds,
+    /// Block heights of a chain producing a block every `block_secs` seconds on average.
+    Blocks { block_secs: u32 },
+}
+
+impl TimeUnit {
+    /// Timestamp units spanning at least `secs` seconds.
+    pub fn from_secs(self, secs: u64) -> u64 {
+        match self {
+            TimeUnit::Seconds => secs,
+            TimeUnit::Milliseconds => secs.saturating_mul(1000),
+            TimeUnit::Blocks { block_secs } => secs.div_ceil(block_secs.max(1) as u64),
+        }
+    }
+
+    /// Seconds spanned by `units` timestamp units.
+    pub fn to_secs(self, units: u64) -> f64 {
+        match self {
+            TimeUnit::Seconds => units as f64,
+            TimeUnit::Milliseconds => units as f64 / 1000.0,
+            TimeUnit::Blocks { block_secs } => units as f64 * block_secs as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(3600, TimeUnit::Seconds.from_secs(3600));
+        assert_eq!(3_600_000, TimeUnit::Milliseconds.from_secs(3600));
+        assert_eq!(6, TimeUnit::Blocks { block_secs: 600 }.from_secs(3600));
+        assert_eq!(300, TimeUnit::Blocks { block_secs: 12 }.from_secs(3599));
+        assert_eq!(1.5, TimeUnit::Milliseconds.to_secs(1500));
+        assert_eq!(1200.0, TimeUnit::Blocks { block_secs: 600 }.to_secs(2));
+    }
+}
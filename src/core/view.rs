@@ -0,0 +1,574 @@
+use crate::core::amount::Amount;
+use crate::core::assets::AssetScope;
+use crate::core::graph::{Graph, IncomingEdgeRef, OutgoingEdgeRef};
+use crate::core::ids::{AssetId, NodeId};
+use crate::core::time::TimeUnit;
+use std::collections::HashSet;
+
+/// Read access to a directed graph, implemented by `Graph` and by the lazy filter adapters
+/// below, which keep the node ids and edge ids of the graph they wrap.
+pub trait GraphView {
+    fn node_count(&self) -> usize;
+
+    fn edges_from(&self, src: NodeId) -> impl Iterator<Item = OutgoingEdgeRef> + '_;
+
+    fn edges_to(&self, dst: NodeId) -> impl Iterator<Item = IncomingEdgeRef> + '_;
+
+    fn asset_scope(&self) -> AssetScope;
+
+    fn time_unit(&self) -> TimeUnit;
+
+    fn out_degree(&self, src: NodeId) -> usize {
+        self.edges_from(src).count()
+    }
+
+    fn in_degree(&self, dst: NodeId) -> usize {
+        self.edges_to(dst).count()
+    }
+
+    /// Whether the edges of every node are ordered by timestamp.
+    fn is_time_sorted(&self) -> bool {
+        false
+    }
+
+    /// Outgoing edges of `src` with a timestamp in `t0..=t1`.
+    fn edges_from_between(
+        &self,
+        src: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.edges_from(src)
+            .filter(move |e| (t0..=t1).contains(&e.timestamp))
+    }
+
+    /// Incoming edges of `dst` with a timestamp in `t0..=t1`.
+    fn edges_to_between(
+        &self,
+        dst: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.edges_to(dst)
+            .filter(move |e| (t0..=t1).contains(&e.timestamp))
+    }
+}
+
+impl GraphView for Graph {
+    fn node_count(&self) -> usize {
+        self.node_count()
+    }
+
+    fn edges_from(&self, src: NodeId) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.edges_from(src)
+    }
+
+    fn edges_to(&self, dst: NodeId) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.edges_to(dst)
+    }
+
+    fn asset_scope(&self) -> AssetScope {
+        self.asset_scope()
+    }
+
+    fn time_unit(&self) -> TimeUnit {
+        self.time_unit()
+    }
+
+    fn out_degree(&self, src: NodeId) -> usize {
+        self.out_degree(src)
+    }
+
+    fn in_degree(&self, dst: NodeId) -> usize {
+        self.in_degree(dst)
+    }
+
+    fn is_time_sorted(&self) -> bool {
+        self.is_time_sorted()
+    }
+
+    fn edges_from_between(
+        &self,
+        src: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.edges_from_between(src, t0, t1)
+    }
+
+    fn edges_to_between(
+        &self,
+        dst: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.edges_to_between(dst, t0, t1)
+    }
+}
+
+impl<G: GraphView> GraphView for &G {
+    fn node_count(&self) -> usize {
+        (**self).node_count()
+    }
+
+    fn edges_from(&self, src: NodeId) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        (**self).edges_from(src)
+    }
+
+    fn edges_to(&self, dst: NodeId) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        (**self).edges_to(dst)
+    }
+
+    fn asset_scope(&self) -> AssetScope {
+        (**self).asset_scope()
+    }
+
+    fn time_unit(&self) -> TimeUnit {
+        (**self).time_unit()
+    }
+
+    fn out_degree(&self, src: NodeId) -> usize {
+        (**self).out_degree(src)
+    }
+
+    fn in_degree(&self, dst: NodeId) -> usize {
+        (**self).in_degree(dst)
+    }
+
+    fn is_time_sorted(&self) -> bool {
+        (**self).is_time_sorted()
+    }
+
+    fn edges_from_between(
+        &self,
+        src: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        (**self).edges_from_between(src, t0, t1)
+    }
+
+    fn edges_to_between(
+        &self,
+        dst: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        (**self).edges_to_between(dst, t0, t1)
+    }
+}
+
+/// Edges transferring at least `min` base units.
+pub struct MinAmount<G> {
+    graph: G,
+    min: Amount,
+}
+
+impl<G: GraphView> MinAmount<G> {
+    pub fn new(graph: G, min: Amount) -> Self {
+        Self { graph, min }
+    }
+}
+
+impl<G: GraphView> GraphView for MinAmount<G> {
+    fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    fn edges_from(&self, src: NodeId) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.graph.edges_from(src).filter(|e| e.amount >= self.min)
+    }
+
+    fn edges_to(&self, dst: NodeId) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.graph.edges_to(dst).filter(|e| e.amount >= self.min)
+    }
+
+    fn asset_scope(&self) -> AssetScope {
+        self.graph.asset_scope()
+    }
+
+    fn time_unit(&self) -> TimeUnit {
+        self.graph.time_unit()
+    }
+
+    fn is_time_sorted(&self) -> bool {
+        self.graph.is_time_sorted()
+    }
+
+    fn edges_from_between(
+        &self,
+        src: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.graph
+            .edges_from_between(src, t0, t1)
+            .filter(|e| e.amount >= self.min)
+    }
+
+    fn edges_to_between(
+        &self,
+        dst: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.graph
+            .edges_to_between(dst, t0, t1)
+            .filter(|e| e.amount >= self.min)
+    }
+}
+
+/// Edges of one asset, whose base units make the amounts comparable.
+pub struct AssetFilter<'a> {
+    graph: &'a Graph,
+    asset: AssetId,
+}
+
+impl<'a> AssetFilter<'a> {
+    pub fn new(graph: &'a Graph, asset: AssetId) -> Self {
+        Self { graph, asset }
+    }
+}
+
+impl GraphView for AssetFilter<'_> {
+    fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    fn edges_from(&self, src: NodeId) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.graph
+            .edges_from(src)
+            .filter(|e| self.graph.edge_asset(e.id) == self.asset)
+    }
+
+    fn edges_to(&self, dst: NodeId) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.graph
+            .edges_to(dst)
+            .filter(|e| self.graph.edge_asset(e.id) == self.asset)
+    }
+
+    fn asset_scope(&self) -> AssetScope {
+        AssetScope::Single(self.asset)
+    }
+
+    fn time_unit(&self) -> TimeUnit {
+        self.graph.time_unit()
+    }
+
+    fn is_time_sorted(&self) -> bool {
+        self.graph.is_time_sorted()
+    }
+
+    fn edges_from_between(
+        &self,
+        src: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.graph
+            .edges_from_between(src, t0, t1)
+            .filter(|e| self.graph.edge_asset(e.id) == self.asset)
+    }
+
+    fn edges_to_between(
+        &self,
+        dst: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.graph
+            .edges_to_between(dst, t0, t1)
+            .filter(|e| self.graph.edge_asset(e.id) == self.asset)
+    }
+}
+
+/// Edges with a timestamp in `t0..=t1`.
+pub struct TimeRange<G> {
+    graph: G,
+    t0: u64,
+    t1: u64,
+}
+
+impl<G: GraphView> TimeRange<G> {
+    pub fn new(graph: G, t0: u64, t1: u64) -> Self {
+        Self { graph, t0, t1 }
+    }
+}
+
+impl<G: GraphView> GraphView for TimeRange<G> {
+    fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    fn edges_from(&self, src: NodeId) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.graph.edges_from_between(src, self.t0, self.t1)
+    }
+
+    fn edges_to(&self, dst: NodeId) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.graph.edges_to_between(dst, self.t0, self.t1)
+    }
+
+    fn asset_scope(&self) -> AssetScope {
+        self.graph.asset_scope()
+    }
+
+    fn time_unit(&self) -> TimeUnit {
+        self.graph.time_unit()
+    }
+
+    fn is_time_sorted(&self) -> bool {
+        self.graph.is_time_sorted()
+    }
+
+    fn edges_from_between(
+        &self,
+        src: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.graph
+            .edges_from_between(src, t0.max(self.t0), t1.min(self.t1))
+    }
+
+    fn edges_to_between(
+        &self,
+        dst: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.graph
+            .edges_to_between(dst, t0.max(self.t0), t1.min(self.t1))
+    }
+}
+
+/// The graph without the edges of the excluded nodes; node ids stay the same and excluded
+/// nodes are left isolated.
+pub struct ExcludeNodes<G> {
+    graph: G,
+    excluded: HashSet<NodeId>,
+}
+
+impl<G: GraphView> ExcludeNodes<G> {
+    pub fn new(graph: G, excluded: impl IntoIterator<Item = NodeId>) -> Self {
+        Self {
+            graph,
+            excluded: excluded.into_iter().collect(),
+        }
+    }
+
+    fn is_excluded(&self, node: NodeId) -> bool {
+        self.excluded.contains(&node)
+    }
+}
+
+impl<G: GraphView> GraphView for ExcludeNodes<G> {
+    fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    fn edges_from(&self, src: NodeId) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        let excluded = self.is_excluded(src);
+        self.graph
+            .edges_from(src)
+            .filter(move |e| !excluded && !self.is_excluded(e.dst))
+    }
+
+    fn edges_to(&self, dst: NodeId) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        let excluded = self.is_excluded(dst);
+        self.graph
+            .edges_to(dst)
+            .filter(move |e| !excluded && !self.is_excluded(e.src))
+    }
+
+    fn asset_scope(&self) -> AssetScope {
+        self.graph.asset_scope()
+    }
+
+    fn time_unit(&self) -> TimeUnit {
+        self.graph.time_unit()
+    }
+
+    fn is_time_sorted(&self) -> bool {
+        self.graph.is_time_sorted()
+    }
+
+    fn edges_from_between(
+        &self,
+        src: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        let excluded = self.is_excluded(src);
+        self.graph
+            .edges_from_between(src, t0, t1)
+            .filter(move |e| !excluded && !self.is_excluded(e.dst))
+    }
+
+    fn edges_to_between(
+        &self,
+        dst: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        let excluded = self.is_excluded(dst);
+        self.graph
+            .edges_to_between(dst, t0, t1)
+            .filter(move |e| !excluded && !self.is_excluded(e.src))
+    }
+}
+
+/// Every edge in both directions: the edges from a node are its outgoing edges followed by its
+/// incoming edges reversed, and the edges to it the other way around. A self-loop is seen twice.
+pub struct Undirected<G> {
+    graph: G,
+}
+
+impl<G: GraphView> Undirected<G> {
+    pub fn new(graph: G) -> Self {
+        Self { graph }
+    }
+}
+
+fn reversed_in(e: IncomingEdgeRef) -> OutgoingEdgeRef {
+    OutgoingEdgeRef::new(e.id, e.src, e.amount, e.timestamp)
+}
+
+fn reversed_out(e: OutgoingEdgeRef) -> IncomingEdgeRef {
+    IncomingEdgeRef::new(e.id, e.dst, e.amount, e.timestamp)
+}
+
+impl<G: GraphView> GraphView for Undirected<G> {
+    fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    fn edges_from(&self, src: NodeId) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.graph
+            .edges_from(src)
+            .chain(self.graph.edges_to(src).map(reversed_in))
+    }
+
+    fn edges_to(&self, dst: NodeId) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.graph
+            .edges_to(dst)
+            .chain(self.graph.edges_from(dst).map(reversed_out))
+    }
+
+    fn asset_scope(&self) -> AssetScope {
+        self.graph.asset_scope()
+    }
+
+    fn time_unit(&self) -> TimeUnit {
+        self.graph.time_unit()
+    }
+
+    fn out_degree(&self, src: NodeId) -> usize {
+        self.graph.out_degree(src) + self.graph.in_degree(src)
+    }
+
+    fn in_degree(&self, dst: NodeId) -> usize {
+        self.out_degree(dst)
+    }
+
+    fn edges_from_between(
+        &self,
+        src: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = OutgoingEdgeRef> + '_ {
+        self.graph
+            .edges_from_between(src, t0, t1)
+            .chain(self.graph.edges_to_between(src, t0, t1).map(reversed_in))
+    }
+
+    fn edges_to_between(
+        &self,
+        dst: NodeId,
+        t0: u64,
+        t1: u64,
+    ) -> impl Iterator<Item = IncomingEdgeRef> + '_ {
+        self.graph
+            .edges_to_between(dst, t0, t1)
+            .chain(self.graph.edges_from_between(dst, t0, t1).map(reversed_out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::components::connected_components;
+    use crate::core::graph::{FreezeConfig, GraphBuilder};
+
+    fn sample(sort_by_time: bool) -> Graph {
+        let mut gb = GraphBuilder::new(4);
+        gb.add_edge(0, 1, 5, 30).unwrap();
+        gb.add_edge(0, 2, 50, 10).unwrap();
+        gb.add_edge(1, 2, 20, 20).unwrap();
+        gb.add_edge(2, 3, 1, 40).unwrap();
+        gb.freeze_with(&FreezeConfig {
+            sort_by_time,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_min_amount() {
+        let g = sample(false);
+        let view = MinAmount::new(&g, 10);
+
+        assert_eq!(4, view.node_count());
+        assert_eq!(1, view.out_degree(0));
+        assert_eq!(2, GraphView::in_degree(&view, 2));
+        assert_eq!(0, view.edges_to(3).count());
+        assert_eq!(vec![0, 0, 0, 1], connected_components(&view));
+    }
+
+    #[test]
+    fn test_time_range() {
+        for sort_by_time in [false, true] {
+            let g = sample(sort_by_time);
+            let view = TimeRange::new(&g, 15, 35);
+
+            assert_eq!(sort_by_time, view.is_time_sorted());
+            let dsts = view.edges_from(0).map(|e| e.dst).collect::<Vec<_>>();
+            assert_eq!(vec![1], dsts);
+            assert_eq!(vec![1], view.edges_to(2).map(|e| e.src).collect::<Vec<_>>());
+            assert_eq!(0, view.edges_from_between(1, 0, 19).count());
+            assert_eq!(1, view.edges_from_between(1, 0, 20).count());
+            assert_eq!(0, view.edges_from_between(2, 10, 50).count());
+            assert_eq!(1, view.edges_to_between(2, 20, 50).count());
+            assert_eq!(0, view.edges_to_between(2, 0, 19).count());
+        }
+    }
+
+    #[test]
+    fn test_exclude_nodes_and_chaining() {
+        let g = sample(false);
+        let view = ExcludeNodes::new(MinAmount::new(&g, 2), [1]);
+
+        assert_eq!(0, view.out_degree(1));
+        assert_eq!(0, view.in_degree(1));
+        let ids = view.edges_to(2).map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(vec![1], ids);
+        assert_eq!(0, view.edges_from(2).count());
+        assert_eq!(vec![0, 1, 0, 2], connected_components(&view));
+    }
+
+    #[test]
+    fn test_undirected() {
+        let g = sample(true);
+        let view = Undirected::new(TimeRange::new(&g, 0, 35));
+
+        assert_eq!((2, 2), (view.out_degree(2), view.in_degree(2)));
+        let mut neighbours = view.edges_from(2).map(|e| e.dst).collect::<Vec<_>>();
+        neighbours.sort();
+        assert_eq!(vec![0, 1], neighbours);
+        let srcs = view.edges_to(1).map(|e| e.src).collect::<Vec<_>>();
+        assert_eq!(vec![0, 2], srcs);
+        assert_eq!(0, view.edges_from(3).count());
+        let ids = view.edges_from_between(2, 15, 50).map(|e| e.id);
+        assert_eq!(vec![2], ids.collect::<Vec<_>>());
+        let srcs = view.edges_to_between(0, 0, 20).map(|e| e.src);
+        assert_eq!(vec![2], srcs.collect::<Vec<_>>());
+    }
+}
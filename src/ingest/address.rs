@@ -0,0 +1,284 @@
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::borrow::Cow;
+
+/// Why an address was rejected by an `AddressNormalizer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AddressError {
+    Empty,
+    /// Wrong length, prefix or characters for the chain.
+    Malformed,
+    /// Well formed, but the embedded checksum does not match.
+    BadChecksum,
+}
+
+/// Validates the addresses of one chain and maps them to a canonical form at registration,
+/// so that spellings of the same address become one node.
+pub trait AddressNormalizer {
+    fn normalize<'a>(&self, address: &'a str) -> Result<Cow<'a, str>, AddressError>;
+}
+
+/// Strips surrounding whitespace, rejecting empty addresses.
+pub struct Trim;
+
+impl AddressNormalizer for Trim {
+    fn normalize<'a>(&self, address: &'a str) -> Result<Cow<'a, str>, AddressError> {
+        match address.trim() {
+            "" => Err(AddressError::Empty),
+            address => Ok(Cow::Borrowed(address)),
+        }
+    }
+}
+
+/// `0x` prefixed 20 byte hex addresses of Ethereum and other EVM chains, lowercased. Mixed case
+/// addresses carry an EIP-55 checksum, which has to match.
+pub struct Evm;
+
+impl AddressNormalizer for Evm {
+    fn normalize<'a>(&self, address: &'a str) -> Result<Cow<'a, str>, AddressError> {
+        let address = Trim.normalize(address)?;
+        let hex = address
+            .strip_prefix("0x")
+            .filter(|hex| hex.len() == 40 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or(AddressError::Malformed)?;
+        let lower = hex.to_ascii_lowercase();
+        let mixed_case = hex.bytes().any(|b| b.is_ascii_lowercase())
+            && hex.bytes().any(|b| b.is_ascii_uppercase());
+        if mixed_case && hex != eip55_checksum(&lower) {
+            return Err(AddressError::BadChecksum);
+        }
+        Ok(Cow::Owned(format!("0x{lower}")))
+    }
+}
+
+// Uppercases every letter whose nibble in the keccak hash of the lowercase hex is 8 or more.
+fn eip55_checksum(lower: &str) -> String {
+    let hash = Keccak256::digest(lower.as_bytes());
+    lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (4 * (1 - i % 2))) & 0xf;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Bitcoin mainnet, testnet and regtest addresses: Bech32 and Bech32m segwit addresses,
+/// lowercased, and Base58Check legacy addresses, kept as they are.
+pub struct Bitcoin;
+
+impl AddressNormalizer for Bitcoin {
+    fn normalize<'a>(&self, address: &'a str) -> Result<Cow<'a, str>, AddressError> {
+        let address = Trim.normalize(address)?;
+        let lower = address.to_ascii_lowercase();
+        if ["bc1", "tb1", "bcrt1"].iter().any(|p| lower.starts_with(p)) {
+            check_segwit(&address)?;
+            return Ok(Cow::Owned(lower));
+        }
+        check_base58(&address)?;
+        Ok(address)
+    }
+}
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+// BIP-173 and BIP-350.
+fn check_segwit(address: &str) -> Result<(), AddressError> {
+    if address.len() > 90
+        || address.bytes().any(|b| b.is_ascii_lowercase())
+            && address.bytes().any(|b| b.is_ascii_uppercase())
+    {
+        return Err(AddressError::Malformed);
+    }
+    let lower = address.to_ascii_lowercase();
+    let (hrp, data) = lower.rsplit_once('1').ok_or(AddressError::Malformed)?;
+    if !["bc", "tb", "bcrt"].contains(&hrp) {
+        return Err(AddressError::Malformed);
+    }
+    let data = data
+        .bytes()
+        .map(|b| BECH32_CHARSET.iter().position(|c| *c == b).map(|v| v as u8))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(AddressError::Malformed)?;
+    if data.len() < 7 {
+        return Err(AddressError::Malformed);
+    }
+
+    let (payload, _) = data.split_at(data.len() - 6);
+    let version = payload[0];
+    let program = convert_bits(&payload[1..]).ok_or(AddressError::Malformed)?;
+    let valid_program = match version {
+        0 => program.len() == 20 || program.len() == 32,
+        1..=16 => (2..=40).contains(&program.len()),
+        _ => false,
+    };
+    if !valid_program {
+        return Err(AddressError::Malformed);
+    }
+
+    let expected = if version == 0 {
+        BECH32_CONST
+    } else {
+        BECH32M_CONST
+    };
+    let values = hrp
+        .bytes()
+        .map(|b| b >> 5)
+        .chain([0])
+        .chain(hrp.bytes().map(|b| b & 0x1f))
+        .chain(data.iter().copied());
+    if bech32_polymod(values) != expected {
+        return Err(AddressError::BadChecksum);
+    }
+    Ok(())
+}
+
+fn bech32_polymod(values: impl Iterator<Item = u8>) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = (checksum & 0x1ff_ffff) << 5 ^ value as u32;
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= g;
+            }
+        }
+    }
+    checksum
+}
+
+// Regroups 5 bit values into bytes, the padding has to be fewer than 5 zero bits.
+fn convert_bits(values: &[u8]) -> Option<Vec<u8>> {
+    let (mut acc, mut bits) = (0u32, 0);
+    let mut bytes = Vec::with_capacity(values.len() * 5 / 8);
+    for value in values {
+        acc = (acc << 5) | *value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+    }
+    (bits < 5 && acc & ((1 << bits) - 1) == 0).then_some(bytes)
+}
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+// P2PKH and P2SH version bytes of mainnet and testnet.
+const BASE58_VERSIONS: [u8; 4] = [0x00, 0x05, 0x6f, 0xc4];
+
+fn check_base58(address: &str) -> Result<(), AddressError> {
+    let bytes = base58_decode(address).ok_or(AddressError::Malformed)?;
+    if bytes.len() != 25 || !BASE58_VERSIONS.contains(&bytes[0]) {
+        return Err(AddressError::Malformed);
+    }
+    let (payload, checksum) = bytes.split_at(21);
+    if Sha256::digest(Sha256::digest(payload))[..4] != *checksum {
+        return Err(AddressError::BadChecksum);
+    }
+    Ok(())
+}
+
+fn base58_decode(text: &str) -> Option<Vec<u8>> {
+    // little-endian base 256 digits of the number
+    let mut digits: Vec<u8> = vec![];
+    for c in text.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|a| *a == c)? as u32;
+        for digit in &mut digits {
+            carry += *digit as u32 * 58;
+            *digit = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            digits.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = text.bytes().take_while(|c| *c == b'1').count();
+    let mut bytes = vec![0; zeros];
+    bytes.extend(digits.iter().rev());
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evm() {
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let lower = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed";
+        assert_eq!(lower, Evm.normalize(checksummed).unwrap());
+        assert_eq!(lower, Evm.normalize(&format!(" {lower}\t")).unwrap());
+        assert_eq!(
+            lower,
+            Evm.normalize(&lower.to_uppercase().replace("0X", "0x"))
+                .unwrap()
+        );
+        assert_eq!(
+            Err(AddressError::BadChecksum),
+            Evm.normalize("0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed")
+        );
+        assert_eq!(Err(AddressError::Malformed), Evm.normalize("0x5aaeb605"));
+        assert_eq!(
+            Err(AddressError::Malformed),
+            Evm.normalize(&lower.replace('a', "g"))
+        );
+        assert_eq!(Err(AddressError::Empty), Evm.normalize("  "));
+    }
+
+    #[test]
+    fn test_bitcoin_segwit() {
+        let valid = [
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+        ];
+        for address in valid {
+            assert_eq!(address.to_lowercase(), Bitcoin.normalize(address).unwrap());
+        }
+        // bech32 checksum on a v1 program, and a flipped character
+        for address in [
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+        ] {
+            assert_eq!(Err(AddressError::BadChecksum), Bitcoin.normalize(address));
+        }
+        // mixed case, and a valid checksum over the unknown HRP `bc1x`
+        for address in [
+            "bc1QW508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "bc1x1qw508d6qejxtdg4y5r3zarvary0c5xw7kglx4m7",
+        ] {
+            assert_eq!(Err(AddressError::Malformed), Bitcoin.normalize(address));
+        }
+    }
+
+    #[test]
+    fn test_bitcoin_base58() {
+        let p2pkh = "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2";
+        assert_eq!(p2pkh, Bitcoin.normalize(p2pkh).unwrap());
+        assert!(
+            Bitcoin
+                .normalize("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy")
+                .is_ok()
+        );
+        assert_eq!(
+            Err(AddressError::BadChecksum),
+            Bitcoin.normalize("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3")
+        );
+        assert_eq!(Err(AddressError::Malformed), Bitcoin.normalize("1BvBMSEY0"));
+    }
+}
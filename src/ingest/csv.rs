@@ -1,10 +1,296 @@
+use crate::core::amount::{self, Amount, AmountError};
+use crate::core::attributes::{AttributeKind, AttributeValue};
+use crate::core::entity::EntityMap;
 use crate::core::graph::GraphBuilder;
-use crate::core::ids::NodeRegistry;
+use crate::core::ids::{DEFAULT_ASSET, NodeId, NodeRegistry};
+use crate::core::memory::MemoryUsage;
+use crate::ingest::address::{AddressError, AddressNormalizer};
+use crate::ingest::timestamp::TimestampFormat;
+use anyhow::{Context, bail};
+use csv::{ByteRecord, StringRecord};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::File;
 use std::io::BufReader;
+use std::path::PathBuf;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RejectReason {
+    /// Not a well formed record, e.g. a CSV record with an unterminated quote or invalid UTF-8,
+    /// or a JSON Lines line that is not valid JSON.
+    MalformedRecord,
+    /// A required column is missing or, in an attribution, empty.
+    MissingField,
+    InvalidAddress(AddressError),
+    /// Attribution of an address that is not in the registry.
+    UnknownAddress,
+    InvalidTimestamp,
+    InvalidAmount(AmountError),
+    /// The amount names another asset than the asset column.
+    AssetMismatch,
+    /// A `U64` attribute field that is not an integer, or a JSON attribute that is neither a
+    /// string nor a number.
+    InvalidAttribute,
+    /// A decimal amount of an asset whose decimals were not set.
+    UnknownAssetScale,
+    /// A new node beyond the node count of a builder that is not growable.
+    NodeOutOfRange,
+    /// A new asset beyond the limit of distinct assets.
+    TooManyAssets,
+}
+
+#[derive(Debug, Default)]
 pub struct IngestStats {
     pub parsed: u64,
+    /// Rows not added to the graph, each counted under its reason in `rejected`.
     pub skipped: u64,
+    pub rejected: BTreeMap<RejectReason, u64>,
+}
+
+impl IngestStats {
+    fn reject(&mut self, reason: RejectReason) {
+        self.skipped += 1;
+        *self.rejected.entry(reason).or_default() += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct IngestConfig {
+    /// Applied to both addresses of every row before registration, rows with an invalid
+    /// address are rejected. Without one addresses are registered as they are.
+    pub normalizer: Option<Box<dyn AddressNormalizer>>,
+    /// Format of the timestamp field, rows with a timestamp not in it are rejected.
+    pub timestamp: TimestampFormat,
+    pub amount: AmountFormat,
+    /// Fail on the first rejected row instead of skipping it.
+    pub strict: bool,
+    /// CSV file receiving every rejected row as its line number, the reason and its fields.
+    pub rejects_path: Option<PathBuf>,
+}
+
+// Counts the rejected rows, copies them to the rejects file and stops a strict ingest.
+pub(crate) struct Rejects {
+    writer: Option<csv::Writer<File>>,
+    strict: bool,
+}
+
+impl Rejects {
+    pub(crate) fn new(cfg: &IngestConfig) -> anyhow::Result<Self> {
+        let writer = match &cfg.rejects_path {
+            Some(path) => {
+                let mut writer = csv::WriterBuilder::new()
+                    .flexible(true)
+                    .from_path(path)
+                    .with_context(|| format!("failed to create {}", path.display()))?;
+                writer.write_record(["line", "reason", "record"])?;
+                Some(writer)
+            }
+            None => None,
+        };
+        Ok(Self {
+            writer,
+            strict: cfg.strict,
+        })
+    }
+
+    pub(crate) fn reject<'a>(
+        &mut self,
+        stats: &mut IngestStats,
+        line: u64,
+        reason: RejectReason,
+        fields: impl IntoIterator<Item = &'a str>,
+    ) -> anyhow::Result<()> {
+        stats.reject(reason);
+        let (line, reason) = (line.to_string(), format!("{reason:?}"));
+        if let Some(writer) = &mut self.writer {
+            writer.write_field(&line)?;
+            writer.write_field(&reason)?;
+            for field in fields {
+                writer.write_field(field)?;
+            }
+            writer.write_record(None::<&[u8]>)?;
+        }
+        if self.strict {
+            self.finish()?;
+            bail!("rejected line {line}: {reason}");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(&mut self) -> anyhow::Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// How the amount field of a record is written. Either way commas may group the integer digits
+/// and the number may be followed by whitespace and the asset's name, e.g. `"1,234.5 USDT"`,
+/// which then names the asset of a row without an asset column. Amounts that do not fit into
+/// base units exactly are rejected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AmountFormat {
+    /// Integer base units of the asset.
+    #[default]
+    BaseUnits,
+    /// Whole units of the asset, scaled to base units by its decimals, see
+    /// `GraphBuilder::set_asset_decimals`. Rows of assets without decimals, the default asset
+    /// included, are rejected.
+    Decimal,
+}
+
+/// Column of a CSV ledger.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CsvColumn {
+    /// Column with this name in the header.
+    Name(String),
+    /// Zero based position.
+    Index(usize),
+}
+
+/// Column stored as an edge attribute.
+#[derive(Clone, Debug)]
+pub struct CsvAttribute {
+    pub column: CsvColumn,
+    /// Registered on the builder unless it already has an attribute of this name and kind.
+    pub name: String,
+    pub kind: AttributeKind,
+}
+
+/// Layout of a CSV ledger. Columns it does not mention are ignored.
+#[derive(Clone, Debug)]
+pub struct CsvSchema {
+    pub delimiter: u8,
+    /// Quote character, without one quotes are read as part of the field.
+    pub quote: Option<u8>,
+    /// The first record names the columns and is not ingested.
+    pub has_header: bool,
+    pub src: CsvColumn,
+    pub dst: CsvColumn,
+    pub amount: CsvColumn,
+    pub timestamp: CsvColumn,
+    /// Name of the transferred asset, rows without this column transfer the default asset.
+    pub asset: Option<CsvColumn>,
+    /// Rows without an attribute's column get its default value.
+    pub attributes: Vec<CsvAttribute>,
+}
+
+impl Default for CsvSchema {
+    /// Comma separated `src,dst,amount,timestamp[,asset]` records after a header.
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: Some(b'"'),
+            has_header: true,
+            src: CsvColumn::Index(0),
+            dst: CsvColumn::Index(1),
+            amount: CsvColumn::Index(2),
+            timestamp: CsvColumn::Index(3),
+            asset: Some(CsvColumn::Index(4)),
+            attributes: vec![],
+        }
+    }
+}
+
+impl CsvSchema {
+    /// Comma separated records with the given header names of the required columns.
+    pub fn named(src: &str, dst: &str, amount: &str, timestamp: &str) -> Self {
+        Self {
+            src: CsvColumn::Name(src.to_string()),
+            dst: CsvColumn::Name(dst.to_string()),
+            amount: CsvColumn::Name(amount.to_string()),
+            timestamp: CsvColumn::Name(timestamp.to_string()),
+            asset: None,
+            ..Default::default()
+        }
+    }
+
+    fn reader<R: std::io::Read>(&self, reader: R) -> csv::Reader<BufReader<R>> {
+        csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quoting(self.quote.is_some())
+            .quote(self.quote.unwrap_or(b'"'))
+            .has_headers(self.has_header)
+            .flexible(true)
+            .from_reader(BufReader::new(reader))
+    }
+}
+
+// Positions of the schema's columns in the records of one file.
+struct ColumnIndices {
+    src: usize,
+    dst: usize,
+    amount: usize,
+    timestamp: usize,
+    asset: Option<usize>,
+    // column of every attribute of the builder, in registration order
+    attributes: Vec<Option<usize>>,
+}
+
+impl ColumnIndices {
+    fn resolve(
+        schema: &CsvSchema,
+        header: Option<&StringRecord>,
+        builder: &mut GraphBuilder,
+    ) -> anyhow::Result<Self> {
+        let position = |column: &CsvColumn| match (column, header) {
+            (CsvColumn::Index(idx), _) => Ok(*idx),
+            (CsvColumn::Name(name), Some(header)) => header
+                .iter()
+                .position(|h| h == name)
+                .with_context(|| format!("no column {name} in the header")),
+            (CsvColumn::Name(name), None) => bail!("column {name} is named but there is no header"),
+        };
+
+        let [src, dst, amount, timestamp] =
+            [&schema.src, &schema.dst, &schema.amount, &schema.timestamp].map(position);
+        let (src, dst, amount, timestamp) = (src?, dst?, amount?, timestamp?);
+        let asset = schema.asset.as_ref().map(position).transpose()?;
+        let columns = schema
+            .attributes
+            .iter()
+            .map(|attr| position(&attr.column))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let named = schema
+            .attributes
+            .iter()
+            .map(|attr| (attr.name.as_str(), attr.kind));
+        let attributes = attribute_sources(builder, named)?
+            .into_iter()
+            .map(|source| source.map(|attr| columns[attr]))
+            .collect();
+
+        Ok(Self {
+            src,
+            dst,
+            amount,
+            timestamp,
+            asset,
+            attributes,
+        })
+    }
+}
+
+// Registers the attributes a schema maps on the builder unless it has them already. Returns for
+// every attribute of the builder, in registration order, the schema attribute feeding it.
+pub(crate) fn attribute_sources<'a>(
+    builder: &mut GraphBuilder,
+    attributes: impl IntoIterator<Item = (&'a str, AttributeKind)>,
+) -> anyhow::Result<Vec<Option<usize>>> {
+    let mut sources = vec![None; builder.attributes.len()];
+    for (source, (name, kind)) in attributes.into_iter().enumerate() {
+        let id = match builder.attributes.iter().position(|a| a.name == name) {
+            Some(id) if builder.attributes[id].kind == kind => id,
+            Some(_) => bail!("attribute {name} is registered with another kind"),
+            None => {
+                sources.push(None);
+                builder.add_attribute(name, kind)?
+            }
+        };
+        sources[id] = Some(source);
+    }
+    Ok(sources)
 }
 
 pub fn ingest_csv<R: std::io::Read>(
@@ -12,37 +298,704 @@ pub fn ingest_csv<R: std::io::Read>(
     builder: &mut GraphBuilder,
     node_registry: &mut NodeRegistry,
 ) -> anyhow::Result<IngestStats> {
+    ingest_csv_with(
+        reader,
+        builder,
+        node_registry,
+        &CsvSchema::default(),
+        &IngestConfig::default(),
+    )
+}
+
+pub fn ingest_csv_with<R: std::io::Read>(
+    reader: R,
+    builder: &mut GraphBuilder,
+    node_registry: &mut NodeRegistry,
+    schema: &CsvSchema,
+    cfg: &IngestConfig,
+) -> anyhow::Result<IngestStats> {
+    cfg.timestamp.check_unit(builder.time_unit)?;
+    let mut csv_reader = schema.reader(reader);
+    let header = match schema.has_header {
+        true => Some(csv_reader.headers()?.clone()),
+        false => None,
+    };
+    let columns = ColumnIndices::resolve(schema, header.as_ref(), builder)?;
+    let mut stats = IngestStats::default();
+    let mut rejects = Rejects::new(cfg)?;
+
+    for maybe_record in csv_reader.byte_records() {
+        let Some(record) = text_record(maybe_record, &mut stats, &mut rejects)? else {
+            continue;
+        };
+        let fields = Fields {
+            src: record.get(columns.src),
+            dst: record.get(columns.dst),
+            amount: record.get(columns.amount),
+            timestamp: record.get(columns.timestamp),
+            asset: columns.asset.and_then(|idx| record.get(idx)),
+            attributes: columns
+                .attributes
+                .iter()
+                .map(|column| column.and_then(|idx| record.get(idx)))
+                .collect(),
+        };
+        let added = match parse_row(fields, builder, cfg) {
+            Ok(row) => add_row(row, builder, node_registry)?,
+            Err(reason) => Err(reason),
+        };
+        match added {
+            Ok(()) => stats.parsed += 1,
+            Err(reason) => {
+                let line = record.position().map_or(0, |p| p.line());
+                rejects.reject(&mut stats, line, reason, &record)?;
+            }
+        }
+    }
+
+    rejects.finish()?;
+    Ok(stats)
+}
+
+// The record as text. A record that cannot be read or is not valid UTF-8 is rejected as
+// malformed, the latter copied lossily to the rejects file.
+fn text_record(
+    record: csv::Result<ByteRecord>,
+    stats: &mut IngestStats,
+    rejects: &mut Rejects,
+) -> anyhow::Result<Option<StringRecord>> {
+    let record = match record {
+        Ok(record) => record,
+        Err(error) => {
+            let line = error.position().map_or(0, |p| p.line());
+            rejects.reject(stats, line, RejectReason::MalformedRecord, [])?;
+            return Ok(None);
+        }
+    };
+    match StringRecord::from_byte_record(record) {
+        Ok(record) => Ok(Some(record)),
+        Err(error) => {
+            let record = error.into_byte_record();
+            let line = record.position().map_or(0, |p| p.line());
+            let fields = record
+                .iter()
+                .map(String::from_utf8_lossy)
+                .collect::<Vec<_>>();
+            let fields = fields.iter().map(|field| &**field);
+            rejects.reject(stats, line, RejectReason::MalformedRecord, fields)?;
+            Ok(None)
+        }
+    }
+}
+
+// Raw fields of one record, `None` where the record lacks them.
+pub(crate) struct Fields<'a> {
+    pub(crate) src: Option<&'a str>,
+    pub(crate) dst: Option<&'a str>,
+    pub(crate) amount: Option<&'a str>,
+    pub(crate) timestamp: Option<&'a str>,
+    pub(crate) asset: Option<&'a str>,
+    // one per attribute of the builder, in registration order
+    pub(crate) attributes: Vec<Option<&'a str>>,
+}
+
+// Fields of a valid row. Nothing is registered while parsing, so a rejected row leaves no
+// nodes or assets behind.
+pub(crate) struct Row<'a> {
+    src: Cow<'a, str>,
+    dst: Cow<'a, str>,
+    asset: Option<&'a str>,
+    amount: Amount,
+    timestamp: u64,
+    attributes: Vec<AttributeValue<'a>>,
+}
+
+pub(crate) fn parse_row<'a>(
+    fields: Fields<'a>,
+    builder: &GraphBuilder,
+    cfg: &IngestConfig,
+) -> Result<Row<'a>, RejectReason> {
+    let (Some(src), Some(dst), Some(amount), Some(timestamp)) =
+        (fields.src, fields.dst, fields.amount, fields.timestamp)
+    else {
+        return Err(RejectReason::MissingField);
+    };
+    let (src, dst) = match &cfg.normalizer {
+        Some(normalizer) => (
+            normalizer
+                .normalize(src)
+                .map_err(RejectReason::InvalidAddress)?,
+            normalizer
+                .normalize(dst)
+                .map_err(RejectReason::InvalidAddress)?,
+        ),
+        None => (Cow::Borrowed(src), Cow::Borrowed(dst)),
+    };
+
+    let (amount, symbol) = match amount.trim().split_once(char::is_whitespace) {
+        Some((amount, symbol)) => (amount, Some(symbol.trim_start())),
+        None => (amount.trim(), None),
+    };
+    let asset = match (fields.asset.filter(|a| !a.is_empty()), symbol) {
+        (Some(asset), Some(symbol)) if asset != symbol => {
+            return Err(RejectReason::AssetMismatch);
+        }
+        (asset, symbol) => asset.or(symbol),
+    };
+    // assets are only registered for accepted rows, a new one has no decimals yet
+    let decimals = match cfg.amount {
+        AmountFormat::Decimal => asset
+            .map_or(Some(DEFAULT_ASSET), |asset| builder.asset_table.get(asset))
+            .and_then(|asset| builder.asset_table.scale(asset))
+            .ok_or(RejectReason::UnknownAssetScale)?,
+        AmountFormat::BaseUnits => 0,
+    };
+    let amount = amount::parse_amount(amount, decimals).map_err(RejectReason::InvalidAmount)?;
+    let timestamp = cfg
+        .timestamp
+        .parse(timestamp, builder.time_unit)
+        .ok_or(RejectReason::InvalidTimestamp)?;
+
+    let attributes = builder
+        .attributes
+        .iter()
+        .zip(fields.attributes)
+        .map(|(attr, field)| match (attr.kind, field) {
+            (AttributeKind::Str, field) => Ok(AttributeValue::Str(field.unwrap_or(""))),
+            (AttributeKind::U64, None) => Ok(AttributeValue::U64(0)),
+            (AttributeKind::U64, Some(field)) => field
+                .parse()
+                .map(AttributeValue::U64)
+                .map_err(|_| RejectReason::InvalidAttribute),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Row {
+        src,
+        dst,
+        asset,
+        amount,
+        timestamp,
+        attributes,
+    })
+}
+
+// Registers the nodes and the asset of an accepted row and adds its edge. The edge is checked
+// first, so an edge the builder does not take leaves no nodes or assets behind. A row the
+// builder cannot take is rejected with the returned reason, only an exceeded memory budget
+// fails the ingest.
+pub(crate) fn add_row(
+    row: Row,
+    builder: &mut GraphBuilder,
+    node_registry: &mut NodeRegistry,
+) -> anyhow::Result<Result<(), RejectReason>> {
+    if builder.budget_check_due() {
+        builder.check_memory_budget(node_registry.heap_bytes())?;
+    }
+    let next = node_registry.len() as NodeId;
+    let src = node_registry.get(&row.src).unwrap_or(next);
+    let dst = match node_registry.get(&row.dst) {
+        Some(dst) => dst,
+        None if row.dst == row.src => src,
+        None => next + (src == next) as NodeId,
+    };
+    if builder.check_attributes(&row.attributes).is_err() {
+        return Ok(Err(RejectReason::InvalidAttribute));
+    }
+    if builder.node_count_with(src, dst).is_none() {
+        return Ok(Err(RejectReason::NodeOutOfRange));
+    }
+    builder.check_nodes(src, dst)?;
+
+    let asset = match row.asset.map(|asset| builder.asset_id(asset)) {
+        Some(Ok(asset)) => asset,
+        Some(Err(_)) => return Ok(Err(RejectReason::TooManyAssets)),
+        None => DEFAULT_ASSET,
+    };
+    let src = node_registry.get_or_insert(&row.src);
+    let dst = node_registry.get_or_insert(&row.dst);
+    builder.add_asset_edge(src, dst, asset, row.amount, row.timestamp, &row.attributes)?;
+    Ok(Ok(()))
+}
+
+/// Reads an attribution file of `address,entity` rows into entities over the nodes of
+/// `node_registry`, see `EntityMap::from_attribution`. Addresses are normalized like in
+/// `ingest_csv_with`, rows of addresses the registry does not know are rejected.
+pub fn ingest_attribution_csv<R: std::io::Read>(
+    reader: R,
+    node_registry: &NodeRegistry,
+    cfg: &IngestConfig,
+) -> anyhow::Result<(EntityMap, IngestStats)> {
     let mut csv_reader = csv::Reader::from_reader(BufReader::new(reader));
-    let mut stats = IngestStats {
-        parsed: 0,
-        skipped: 0,
-    };
-
-    for maybe_record in csv_reader.records() {
-        match maybe_record {
-            Ok(record) if record.len() == 4 => {
-                let src = node_registry.get_or_insert(&record[0]);
-                let dst = node_registry.get_or_insert(&record[1]);
-                let amount = match record[2].parse::<u64>() {
-                    Ok(res) => res,
-                    Err(_) => {
-                        stats.skipped += 1;
-                        continue;
-                    }
-                };
-                let timestamp = match record[3].parse::<u64>() {
-                    Ok(res) => res,
-                    Err(_) => {
-                        stats.skipped += 1;
-                        continue;
-                    }
-                };
-                builder.add_edge(src, dst, amount, timestamp);
+    let mut stats = IngestStats::default();
+    let mut rejects = Rejects::new(cfg)?;
+    let mut attribution = vec![];
+
+    for maybe_record in csv_reader.byte_records() {
+        let Some(record) = text_record(maybe_record, &mut stats, &mut rejects)? else {
+            continue;
+        };
+        let node = match (record.get(0), record.get(1)) {
+            (Some(address), Some(entity)) if !entity.is_empty() => match &cfg.normalizer {
+                Some(normalizer) => normalizer
+                    .normalize(address)
+                    .map_err(RejectReason::InvalidAddress)
+                    .and_then(|a| node_registry.get(&a).ok_or(RejectReason::UnknownAddress)),
+                None => node_registry
+                    .get(address)
+                    .ok_or(RejectReason::UnknownAddress),
+            },
+            _ => Err(RejectReason::MissingField),
+        };
+        match node {
+            Ok(node) => {
+                attribution.push((node, record[1].to_string()));
                 stats.parsed += 1;
             }
-            _ => stats.skipped += 1,
+            Err(reason) => {
+                let line = record.position().map_or(0, |p| p.line());
+                rejects.reject(&mut stats, line, reason, &record)?;
+            }
+        }
+    }
+    rejects.finish()?;
+
+    let entities = EntityMap::from_attribution(
+        node_registry,
+        attribution
+            .iter()
+            .map(|(node, name)| (*node, name.as_str())),
+    )?;
+    Ok((entities, stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::assets::AssetScope;
+    use crate::core::ids::AssetId;
+    use crate::core::memory::MemoryBudget;
+    use crate::core::time::TimeUnit;
+    use crate::ingest::address::Evm;
+
+    #[test]
+    fn test_ingest() {
+        let data = "src,dst,amount,timestamp\na,b,10,100\nb,c,x,200\nc,a,30,300\n";
+        let mut gb = GraphBuilder::new(3);
+        let mut registry = NodeRegistry::new();
+
+        let stats = ingest_csv(data.as_bytes(), &mut gb, &mut registry).unwrap();
+        assert_eq!(2, stats.parsed);
+        assert_eq!(1, stats.skipped);
+        let g = gb.freeze();
+        assert_eq!(2, g.edge_count());
+        let a = registry.get("a").unwrap();
+        assert_eq!(
+            vec![10],
+            g.edges_from(a).map(|e| e.amount).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_asset_column() {
+        let data = "src,dst,amount,timestamp,asset\na,b,10,100,ETH\nb,a,20,200,USDT\n";
+        let mut gb = GraphBuilder::new(2);
+        let mut registry = NodeRegistry::new();
+
+        let stats = ingest_csv(data.as_bytes(), &mut gb, &mut registry).unwrap();
+        assert_eq!(2, stats.parsed);
+        let g = gb.freeze();
+        assert_eq!(AssetScope::Mixed, g.asset_scope());
+        let a = registry.get("a").unwrap();
+        let e = g.edges_from(a).next().unwrap();
+        assert_eq!(Some("ETH"), g.asset_name(g.edge_asset(e.id)));
+    }
+
+    #[test]
+    fn test_named_columns() {
+        let data = "block;hash;to;from;value;time;fee\n\
+            7;\"0x;01\";b;a;10;100;3\n\
+            8;0x02;c;b;20;200\n\
+            9;0x03;a;c;30;300;x\n";
+        let mut schema = CsvSchema::named("from", "to", "value", "time");
+        schema.delimiter = b';';
+        schema.attributes = vec![
+            CsvAttribute {
+                column: CsvColumn::Name("hash".to_string()),
+                name: "tx_hash".to_string(),
+                kind: AttributeKind::Str,
+            },
+            CsvAttribute {
+                column: CsvColumn::Name("fee".to_string()),
+                name: "fee".to_string(),
+                kind: AttributeKind::U64,
+            },
+        ];
+        let mut gb = GraphBuilder::growable();
+        let mut registry = NodeRegistry::new();
+
+        let stats = ingest_csv_with(
+            data.as_bytes(),
+            &mut gb,
+            &mut registry,
+            &schema,
+            &IngestConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(2, stats.parsed);
+        assert_eq!(1, stats.skipped);
+        let g = gb.freeze();
+        let [a, b] = ["a", "b"].map(|id| registry.get(id).unwrap());
+        let e = g.edges_from(a).next().unwrap();
+        assert_eq!((b, 10, 100), (e.dst, e.amount, e.timestamp));
+        assert_eq!(AttributeValue::Str("0x;01"), g.attribute(0, e.id));
+        assert_eq!(AttributeValue::U64(3), g.attribute(1, e.id));
+        let e = g.edges_from(b).next().unwrap();
+        assert_eq!(AttributeValue::U64(0), g.attribute(1, e.id));
+
+        schema.amount = CsvColumn::Name("amount".to_string());
+        let error = ingest_csv_with(
+            data.as_bytes(),
+            &mut GraphBuilder::growable(),
+            &mut NodeRegistry::new(),
+            &schema,
+            &IngestConfig::default(),
+        )
+        .unwrap_err();
+        assert_eq!("no column amount in the header", error.to_string());
+    }
+
+    #[test]
+    fn test_headerless_positions() {
+        let data = "x\t100\ta\tb\t10\ny\t200\tb\ta\t\"20\"\n";
+        let schema = CsvSchema {
+            delimiter: b'\t',
+            quote: None,
+            has_header: false,
+            src: CsvColumn::Index(2),
+            dst: CsvColumn::Index(3),
+            amount: CsvColumn::Index(4),
+            timestamp: CsvColumn::Index(1),
+            asset: None,
+            attributes: vec![],
+        };
+        let mut gb = GraphBuilder::growable();
+        let mut registry = NodeRegistry::new();
+
+        let stats = ingest_csv_with(
+            data.as_bytes(),
+            &mut gb,
+            &mut registry,
+            &schema,
+            &IngestConfig::default(),
+        )
+        .unwrap();
+        // without quoting the quotes are part of the amount
+        assert_eq!(1, stats.parsed);
+        assert_eq!(1, stats.skipped);
+        let e = gb.freeze().edges_from(0).next().unwrap();
+        assert_eq!((1, 10, 100), (e.dst, e.amount, e.timestamp));
+    }
+
+    #[test]
+    fn test_decimal_amounts() {
+        let data = "src,dst,amount,timestamp,asset\n\
+            a,b,\"1,234.50\",1,USDT\n\
+            b,c,0.0134 BTC,2,\n\
+            c,a,0.0134 BTC,3,BTC\n\
+            a,c,0.000000001 BTC,4,\n\
+            b,a,2 BTC,5,USDT\n\
+            c,b,1e3,6,USDT\n\
+            a,b,340282366920938463463374607431768211455 USDT,7,\n\
+            b,c,1,8,ETH\n\
+            c,a,1,9,\n";
+        let mut gb = GraphBuilder::growable();
+        let usdt = gb.asset_id("USDT").unwrap();
+        gb.set_asset_decimals(usdt, 6).unwrap();
+        let btc = gb.asset_id("BTC").unwrap();
+        gb.set_asset_decimals(btc, 8).unwrap();
+        let mut registry = NodeRegistry::new();
+        let cfg = IngestConfig {
+            amount: AmountFormat::Decimal,
+            ..Default::default()
+        };
+
+        let stats = ingest_csv_with(
+            data.as_bytes(),
+            &mut gb,
+            &mut registry,
+            &CsvSchema::default(),
+            &cfg,
+        )
+        .unwrap();
+        assert_eq!(3, stats.parsed);
+        assert_eq!(
+            BTreeMap::from([
+                (RejectReason::InvalidAmount(AmountError::Malformed), 1),
+                (RejectReason::InvalidAmount(AmountError::PrecisionLoss), 1),
+                (RejectReason::InvalidAmount(AmountError::Overflow), 1),
+                (RejectReason::AssetMismatch, 1),
+                (RejectReason::UnknownAssetScale, 2),
+            ]),
+            stats.rejected
+        );
+        let g = gb.freeze();
+        assert_eq!(None, g.asset_id("ETH"));
+        let amounts = |src| {
+            g.edges_from(registry.get(src).unwrap())
+                .map(|e| (g.edge_asset(e.id), e.amount))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![(usdt, 1_234_500_000)], amounts("a"));
+        assert_eq!(vec![(btc, 1_340_000)], amounts("b"));
+        assert_eq!(vec![(btc, 1_340_000)], amounts("c"));
+    }
+
+    #[test]
+    fn test_timestamp_format() {
+        let data = "src,dst,amount,timestamp\n\
+            a,b,1,2024-03-01T12:30:00.250+01:00\n\
+            b,c,2,2024-03-01 11:30:01Z\n\
+            c,a,3,1709292601000\n";
+        let mut gb = GraphBuilder::growable();
+        gb.set_time_unit(TimeUnit::Milliseconds);
+        let mut registry = NodeRegistry::new();
+        let cfg = IngestConfig {
+            timestamp: TimestampFormat::Rfc3339,
+            ..Default::default()
+        };
+
+        let stats = ingest_csv_with(
+            data.as_bytes(),
+            &mut gb,
+            &mut registry,
+            &CsvSchema::default(),
+            &cfg,
+        )
+        .unwrap();
+        assert_eq!(2, stats.parsed);
+        assert_eq!(
+            BTreeMap::from([(RejectReason::InvalidTimestamp, 1)]),
+            stats.rejected
+        );
+        let g = gb.freeze();
+        assert_eq!(TimeUnit::Milliseconds, g.time_unit());
+        let e = g.edges_from(registry.get("a").unwrap()).next().unwrap();
+        assert_eq!(1_709_292_600_250, e.timestamp);
+
+        let mut blocks = GraphBuilder::growable();
+        blocks.set_time_unit(TimeUnit::Blocks { block_secs: 12 });
+        let result = ingest_csv_with(
+            data.as_bytes(),
+            &mut blocks,
+            &mut NodeRegistry::new(),
+            &CsvSchema::default(),
+            &cfg,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_address_normalizer() {
+        let data = "src,dst,amount,timestamp\n\
+            0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed,0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359,1,1\n\
+            0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed,0xFB6916095CA1DF60BB79CE92CE3EA74C37C5D359,2,2\n\
+            0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed,0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359,3,3\n\
+            0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed,0xfb69,4,4\n";
+        let mut gb = GraphBuilder::growable();
+        let mut registry = NodeRegistry::new();
+        let cfg = IngestConfig {
+            normalizer: Some(Box::new(Evm)),
+            ..Default::default()
+        };
+
+        let stats = ingest_csv_with(
+            data.as_bytes(),
+            &mut gb,
+            &mut registry,
+            &CsvSchema::default(),
+            &cfg,
+        )
+        .unwrap();
+        assert_eq!(2, stats.parsed);
+        assert_eq!(2, stats.skipped);
+        assert_eq!(
+            BTreeMap::from([
+                (RejectReason::InvalidAddress(AddressError::Malformed), 1),
+                (RejectReason::InvalidAddress(AddressError::BadChecksum), 1),
+            ]),
+            stats.rejected
+        );
+        assert_eq!(2, registry.len());
+        assert_eq!(
+            Some(0),
+            registry.get("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed")
+        );
+    }
+
+    #[test]
+    fn test_attribution() {
+        let data = "src,dst,amount,timestamp\na1,b,10,100\na2,b,20,200\n";
+        let mut gb = GraphBuilder::growable();
+        let mut registry = NodeRegistry::new();
+        ingest_csv(data.as_bytes(), &mut gb, &mut registry).unwrap();
+
+        let attribution = "address,entity\na1,Exchange\na2,Exchange\nz,Exchange\nb,\n";
+        let (entities, stats) =
+            ingest_attribution_csv(attribution.as_bytes(), &registry, &IngestConfig::default())
+                .unwrap();
+        assert_eq!(2, stats.parsed);
+        assert_eq!(2, stats.skipped);
+        assert_eq!(
+            BTreeMap::from([
+                (RejectReason::MissingField, 1),
+                (RejectReason::UnknownAddress, 1)
+            ]),
+            stats.rejected
+        );
+        assert_eq!(2, entities.entity_count());
+        assert_eq!(
+            vec![0, 1, 0],
+            (0..3).map(|n| entities.entity_of(n)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_rejects_file() {
+        let data = b"src,dst,amount,timestamp\n\
+            a,b,10,100\n\
+            c,d,x,200\n\
+            e,f\n\
+            b,\"a\",\"1,5\",300\n\
+            g,h\xff,1,400\n";
+        let path = std::env::temp_dir().join(format!("traceloc-rejects-{}", std::process::id()));
+        let mut gb = GraphBuilder::growable();
+        let mut registry = NodeRegistry::new();
+        let cfg = IngestConfig {
+            rejects_path: Some(path.clone()),
+            ..Default::default()
+        };
+
+        let stats = ingest_csv_with(
+            &data[..],
+            &mut gb,
+            &mut registry,
+            &CsvSchema::default(),
+            &cfg,
+        )
+        .unwrap();
+        assert_eq!((1, 4), (stats.parsed, stats.skipped));
+        // rejected rows register no nodes
+        assert_eq!(2, registry.len());
+        assert_eq!(None, registry.get("c"));
+        let rejects = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            "line,reason,record\n\
+             3,InvalidAmount(Malformed),c,d,x,200\n\
+             4,MissingField,e,f\n\
+             5,InvalidAmount(Malformed),b,a,\"1,5\",300\n\
+             6,MalformedRecord,g,h\u{fffd},1,400\n",
+            rejects
+        );
+    }
+
+    #[test]
+    fn test_strict() {
+        let data = "src,dst,amount,timestamp\na,b,10,100\nb,c,20,2024\nc,d,x,300\n";
+        let cfg = IngestConfig {
+            strict: true,
+            ..Default::default()
+        };
+        let mut gb = GraphBuilder::growable();
+        let mut registry = NodeRegistry::new();
+
+        let error = ingest_csv_with(
+            data.as_bytes(),
+            &mut gb,
+            &mut registry,
+            &CsvSchema::default(),
+            &cfg,
+        )
+        .unwrap_err();
+        assert_eq!(
+            "rejected line 4: InvalidAmount(Malformed)",
+            error.to_string()
+        );
+        assert_eq!(3, registry.len());
+    }
+
+    #[test]
+    fn test_rows_the_builder_rejects() {
+        let data = "src,dst,amount,timestamp\na,b,10,100\nb,c,20 NEW,200\nc,d,30,300\n";
+        let path = std::env::temp_dir().join(format!("traceloc-full-{}", std::process::id()));
+        let mut gb = GraphBuilder::new(3);
+        for asset in 1..=AssetId::MAX {
+            gb.asset_id(&asset.to_string()).unwrap();
         }
+        let mut registry = NodeRegistry::new();
+        let cfg = IngestConfig {
+            rejects_path: Some(path.clone()),
+            ..Default::default()
+        };
+
+        let stats = ingest_csv_with(
+            data.as_bytes(),
+            &mut gb,
+            &mut registry,
+            &CsvSchema::default(),
+            &cfg,
+        )
+        .unwrap();
+        let rejects = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((1, 2), (stats.parsed, stats.skipped));
+        assert_eq!(
+            "line,reason,record\n\
+             3,TooManyAssets,b,c,20 NEW,200\n\
+             4,NodeOutOfRange,c,d,30,300\n",
+            rejects
+        );
+        assert_eq!(2, registry.len());
     }
 
-    anyhow::Ok(stats)
+    #[test]
+    fn test_growable_builder() {
+        let data = "src,dst,amount,timestamp\na,b,10,100\nb,c,20,200\n";
+        let mut registry = NodeRegistry::new();
+
+        let mut gb = GraphBuilder::new(2);
+        let stats = ingest_csv(data.as_bytes(), &mut gb, &mut registry).unwrap();
+        assert_eq!(
+            (1, Some(&1)),
+            (
+                stats.parsed,
+                stats.rejected.get(&RejectReason::NodeOutOfRange)
+            )
+        );
+        // the row that does not fit registers no nodes
+        assert_eq!(2, registry.len());
+        assert_eq!(None, registry.get("c"));
+
+        let mut gb = GraphBuilder::growable();
+        let mut registry = NodeRegistry::new();
+        ingest_csv(data.as_bytes(), &mut gb, &mut registry).unwrap();
+        gb.reserve_nodes(registry.len());
+        let g = gb.freeze();
+        assert_eq!(3, g.node_count());
+        assert_eq!(2, g.edge_count());
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        // the fifth row grows the edge columns and is checked
+        let data = "src,dst,amount,timestamp\na,b,10,100\nb,c,20,200\nc,d,30,300\n\
+                    d,e,40,400\ne,f,50,500\n";
+        let mut gb = GraphBuilder::growable();
+        gb.set_memory_budget(Some(MemoryBudget::new(128)));
+        let mut registry = NodeRegistry::new();
+
+        let error = ingest_csv(data.as_bytes(), &mut gb, &mut registry).unwrap_err();
+        assert!(error.to_string().contains("memory budget"));
+    }
 }
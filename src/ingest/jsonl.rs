@@ -0,0 +1,234 @@
+use crate::core::attributes::AttributeKind;
+use crate::core::graph::GraphBuilder;
+use crate::core::ids::NodeRegistry;
+use crate::ingest::csv::{
+    Fields, IngestConfig, IngestStats, RejectReason, Rejects, add_row, attribute_sources, parse_row,
+};
+use serde_json::Value;
+use std::io::BufRead;
+
+/// Field stored as an edge attribute.
+#[derive(Clone, Debug)]
+pub struct JsonAttribute {
+    pub path: String,
+    pub name: String,
+    pub kind: AttributeKind,
+}
+
+/// Layout of the objects of a JSON Lines ledger, one transfer per line. Fields are addressed by
+/// dot separated paths of object keys and array indexes, such as `tx.from` or `logs.0.value`.
+/// A field is a string or a number, a number is taken exactly as written; a line with an
+/// attribute that is a bool, object or array is rejected as `InvalidAttribute`. Fields it does
+/// not mention are ignored.
+#[derive(Clone, Debug)]
+pub struct JsonSchema {
+    pub src: String,
+    pub dst: String,
+    pub amount: String,
+    pub timestamp: String,
+    /// Name of the asset, the default asset where the object lacks it.
+    pub asset: Option<String>,
+    pub attributes: Vec<JsonAttribute>,
+}
+
+impl Default for JsonSchema {
+    /// Top level `src`, `dst`, `amount`, `timestamp` and `asset` keys.
+    fn default() -> Self {
+        Self {
+            src: "src".into(),
+            dst: "dst".into(),
+            amount: "amount".into(),
+            timestamp: "timestamp".into(),
+            asset: Some("asset".into()),
+            attributes: vec![],
+        }
+    }
+}
+
+// Text of the field at `path`, `None` if it is missing or null. A value without a text, a bool,
+// object or array, is returned as the error.
+fn field<'a>(object: &'a Value, path: &str) -> Result<Option<&'a str>, &'a Value> {
+    let value = path.split('.').try_fold(object, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => None,
+    });
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(text)) => Ok(Some(text)),
+        Some(Value::Number(number)) => Ok(Some(number.as_str())),
+        Some(other) => Err(other),
+    }
+}
+
+/// Reads newline-delimited JSON transfers into the builder like `ingest_csv_with` reads CSV
+/// rows, with the same normalization, amount and timestamp formats and rejection of invalid
+/// lines. Blank lines are skipped, a line that is not a JSON value is a `MalformedRecord`.
+pub fn ingest_jsonl<R: BufRead>(
+    reader: R,
+    builder: &mut GraphBuilder,
+    node_registry: &mut NodeRegistry,
+    schema: &JsonSchema,
+    cfg: &IngestConfig,
+) -> anyhow::Result<IngestStats> {
+    cfg.timestamp.check_unit(builder.time_unit)?;
+    let named = schema
+        .attributes
+        .iter()
+        .map(|attr| (attr.name.as_str(), attr.kind));
+    let sources = attribute_sources(builder, named)?;
+    let mut stats = IngestStats::default();
+    let mut rejects = Rejects::new(cfg)?;
+
+    for (idx, maybe_line) in reader.split(b'\n').enumerate() {
+        let bytes = maybe_line?;
+        let line = idx as u64 + 1;
+        let text = String::from_utf8_lossy(bytes.strip_suffix(b"\r").unwrap_or(&bytes));
+        if text.trim().is_empty() {
+            continue;
+        }
+        let object = match serde_json::from_slice::<Value>(&bytes) {
+            Ok(object) => object,
+            Err(_) => {
+                rejects.reject(&mut stats, line, RejectReason::MalformedRecord, [&*text])?;
+                continue;
+            }
+        };
+        let attributes = sources
+            .iter()
+            .map(|source| match source {
+                Some(a) => field(&object, &schema.attributes[*a].path),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>, _>>();
+        let Ok(attributes) = attributes else {
+            rejects.reject(&mut stats, line, RejectReason::InvalidAttribute, [&*text])?;
+            continue;
+        };
+        let text_field = |path: &str| field(&object, path).ok().flatten();
+        let fields = Fields {
+            src: text_field(&schema.src),
+            dst: text_field(&schema.dst),
+            amount: text_field(&schema.amount),
+            timestamp: text_field(&schema.timestamp),
+            asset: schema.asset.as_deref().and_then(text_field),
+            attributes,
+        };
+        let added = match parse_row(fields, builder, cfg) {
+            Ok(row) => add_row(row, builder, node_registry)?,
+            Err(reason) => Err(reason),
+        };
+        match added {
+            Ok(()) => stats.parsed += 1,
+            Err(reason) => rejects.reject(&mut stats, line, reason, [&*text])?,
+        }
+    }
+
+    rejects.finish()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::amount::AmountError;
+    use crate::core::attributes::AttributeValue;
+    use crate::ingest::csv::AmountFormat;
+    use crate::ingest::timestamp::TimestampFormat;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_ingest_jsonl() {
+        let data = r#"{"src":"a","dst":"b","amount":10,"timestamp":100}
+{"src":"b","dst":"c","amount":"20","timestamp":"200","asset":"USDT"}
+
+{"src":"c","dst":"a","amount":340282366920938463463374607431768211455,"timestamp":300}
+{"src":"c","dst":"d","amount":5}
+{"src":"c","dst":null,"amount":5,"timestamp":400}
+{"src":"c","dst":"d","amount":5.5,"timestamp":500}
+{"src":"c",
+"#;
+        let mut gb = GraphBuilder::growable();
+        let mut registry = NodeRegistry::new();
+
+        let stats = ingest_jsonl(
+            data.as_bytes(),
+            &mut gb,
+            &mut registry,
+            &JsonSchema::default(),
+            &IngestConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(3, stats.parsed);
+        assert_eq!(
+            BTreeMap::from([
+                (RejectReason::MalformedRecord, 1),
+                (RejectReason::MissingField, 2),
+                (RejectReason::InvalidAmount(AmountError::PrecisionLoss), 1),
+            ]),
+            stats.rejected
+        );
+        assert_eq!(3, registry.len());
+        let g = gb.freeze();
+        let c = registry.get("c").unwrap();
+        let e = g.edges_from(c).next().unwrap();
+        assert_eq!((u128::MAX, 300), (e.amount, e.timestamp));
+        let b = registry.get("b").unwrap();
+        let e = g.edges_from(b).next().unwrap();
+        assert_eq!(Some("USDT"), g.asset_name(g.edge_asset(e.id)));
+    }
+
+    #[test]
+    fn test_nested_paths() {
+        let data = r#"{"tx":{"from":"a","to":"b","hash":"0x01"},"value":"1.5 ETH","time":"2024-03-01T00:00:00Z","logs":[{"index":3}]}
+{"tx":{"from":"b","to":"c"},"value":"2 ETH","time":"2024-03-01T00:00:01Z","logs":[{"index":"x"}]}
+{"tx":{"from":"b","to":"c","hash":true},"value":"2 ETH","time":"2024-03-01T00:00:02Z"}
+{"tx":{"from":"b","to":"c","hash":["0x02"]},"value":"2 ETH","time":"2024-03-01T00:00:03Z"}
+{"tx":{"from":"b","to":"c","hash":null},"value":"3 ETH","time":"2024-03-01T00:00:04Z","logs":[]}
+"#;
+        let schema = JsonSchema {
+            src: "tx.from".into(),
+            dst: "tx.to".into(),
+            amount: "value".into(),
+            timestamp: "time".into(),
+            asset: None,
+            attributes: vec![
+                JsonAttribute {
+                    path: "tx.hash".into(),
+                    name: "tx_hash".into(),
+                    kind: AttributeKind::Str,
+                },
+                JsonAttribute {
+                    path: "logs.0.index".into(),
+                    name: "log_index".into(),
+                    kind: AttributeKind::U64,
+                },
+            ],
+        };
+        let cfg = IngestConfig {
+            timestamp: TimestampFormat::Rfc3339,
+            amount: AmountFormat::Decimal,
+            ..Default::default()
+        };
+        let mut gb = GraphBuilder::growable();
+        let eth = gb.asset_id("ETH").unwrap();
+        gb.set_asset_decimals(eth, 18).unwrap();
+        let mut registry = NodeRegistry::new();
+
+        let stats = ingest_jsonl(data.as_bytes(), &mut gb, &mut registry, &schema, &cfg).unwrap();
+        assert_eq!((2, 3), (stats.parsed, stats.skipped));
+        assert_eq!(
+            Some(&3),
+            stats.rejected.get(&RejectReason::InvalidAttribute)
+        );
+        let g = gb.freeze();
+        let e = g.edges_from(registry.get("a").unwrap()).next().unwrap();
+        assert_eq!(1_500_000_000_000_000_000, e.amount);
+        assert_eq!(1_709_251_200, e.timestamp);
+        assert_eq!(AttributeValue::Str("0x01"), g.attribute(0, e.id));
+        assert_eq!(AttributeValue::U64(3), g.attribute(1, e.id));
+        let e = g.edges_from(registry.get("b").unwrap()).next().unwrap();
+        assert_eq!(AttributeValue::Str(""), g.attribute(0, e.id));
+        assert_eq!(AttributeValue::U64(0), g.attribute(1, e.id));
+    }
+}
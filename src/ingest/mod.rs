@@ -1,2 +1,5 @@
+pub mod address;
 pub mod csv;
+pub mod jsonl;
 pub mod synthetic;
+pub mod timestamp;
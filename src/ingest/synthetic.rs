@@ -1,4 +1,5 @@
 use crate::analysis::mixer::MixerConfig;
+use crate::core::amount::Amount;
 use crate::core::graph::{Graph, GraphBuilder};
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
@@ -12,7 +13,7 @@ pub struct SyntheticConfig {
 pub struct SyntheticEdge {
     pub src: u32,
     pub dst: u32,
-    pub amount: u64,
+    pub amount: Amount,
     pub timestamp: u64,
 }
 
@@ -43,8 +44,8 @@ pub fn star_graph(node_count: u32) -> Graph {
     let mut gb = GraphBuilder::new(node_count as usize);
 
     for leaf in 1..node_count {
-        gb.add_edge(0, leaf, 1, 0);
-        gb.add_edge(leaf, 0, 1, 0);
+        gb.add_edge(0, leaf, 1, 0).unwrap();
+        gb.add_edge(leaf, 0, 1, 0).unwrap();
     }
 
     gb.freeze()
@@ -52,32 +53,32 @@ pub fn star_graph(node_count: u32) -> Graph {
 
 pub fn normal_user_graph(cfg: &MixerConfig) -> Graph {
     let mut gb = GraphBuilder::new(3);
-    gb.add_edge(0, 1, 1, 0);
-    gb.add_edge(2, 0, 1, cfg.window_secs * 2);
+    gb.add_edge(0, 1, 1, 0).unwrap();
+    gb.add_edge(2, 0, 1, cfg.window_secs * 2).unwrap();
     gb.freeze()
 }
 
 pub fn bridge_node_graph(cfg: &MixerConfig) -> Graph {
     let mut gb = GraphBuilder::new(7);
     // Group A
-    gb.add_edge(1, 2, 1, 0);
-    gb.add_edge(2, 3, 1, 0);
+    gb.add_edge(1, 2, 1, 0).unwrap();
+    gb.add_edge(2, 3, 1, 0).unwrap();
     // Group B
-    gb.add_edge(4, 5, 1, 0);
-    gb.add_edge(5, 6, 1, 0);
+    gb.add_edge(4, 5, 1, 0).unwrap();
+    gb.add_edge(5, 6, 1, 0).unwrap();
     // Bridge node = 0
-    gb.add_edge(3, 0, 1, 100);
-    gb.add_edge(0, 4, 1, 100 + cfg.window_secs / 2);
+    gb.add_edge(3, 0, 1, 100).unwrap();
+    gb.add_edge(0, 4, 1, 100 + cfg.window_secs / 2).unwrap();
     gb.freeze()
 }
 
 pub fn exchange_hub_graph(cfg: &MixerConfig) -> Graph {
     let mut gb = GraphBuilder::new(((cfg.deg_threshold as usize + 2) * 2) + 1);
     for i in 1..=cfg.deg_threshold + 2 {
-        gb.add_edge(i, 0, 1, 0);
+        gb.add_edge(i, 0, 1, 0).unwrap();
     }
     for i in cfg.deg_threshold + 3..=(cfg.deg_threshold + 2) * 2 {
-        gb.add_edge(0, i, 1, cfg.window_secs * 10);
+        gb.add_edge(0, i, 1, cfg.window_secs * 10).unwrap();
     }
     gb.freeze()
 }
@@ -86,23 +87,23 @@ pub fn strong_mixer_graph(cfg: &MixerConfig) -> Graph {
     let mut gb = GraphBuilder::new(cfg.deg_threshold as usize * 3 + 1);
 
     for i in 1..=cfg.deg_threshold {
-        gb.add_edge(i, 0, 1, 100);
+        gb.add_edge(i, 0, 1, 100).unwrap();
     }
     for i in cfg.deg_threshold + 1..=cfg.deg_threshold * 2 {
-        gb.add_edge(i, 0, 1, 100);
+        gb.add_edge(i, 0, 1, 100).unwrap();
     }
     for i in cfg.deg_threshold * 2 + 1..=cfg.deg_threshold * 3 {
-        gb.add_edge(i, 0, 1, 100);
+        gb.add_edge(i, 0, 1, 100).unwrap();
     }
 
     for i in 1..=cfg.deg_threshold {
-        gb.add_edge(0, i, 1, 100 + cfg.window_secs / 2);
+        gb.add_edge(0, i, 1, 100 + cfg.window_secs / 2).unwrap();
     }
     for i in cfg.deg_threshold + 1..=cfg.deg_threshold * 2 {
-        gb.add_edge(0, i, 1, 100 + cfg.window_secs / 2);
+        gb.add_edge(0, i, 1, 100 + cfg.window_secs / 2).unwrap();
     }
     for i in cfg.deg_threshold * 2 + 1..=cfg.deg_threshold * 3 {
-        gb.add_edge(0, i, 1, 100 + cfg.window_secs / 2);
+        gb.add_edge(0, i, 1, 100 + cfg.window_secs / 2).unwrap();
     }
 
     gb.freeze()
@@ -0,0 +1,191 @@
+use crate::core::time::TimeUnit;
+use anyhow::bail;
+
+/// How the timestamp field of a record is written. Parsed timestamps are converted into the
+/// `TimeUnit` of the builder they are added to, see `GraphBuilder::set_time_unit`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// Integer seconds since the Unix epoch.
+    #[default]
+    EpochSecs,
+    /// Integer milliseconds since the Unix epoch.
+    EpochMillis,
+    /// RFC 3339 date and time with a `Z` or `+hh:mm` zone offset, such as
+    /// `2024-03-01T12:30:00.250+01:00`. A space may separate date and time and precede the
+    /// offset, and the offset may omit its colon.
+    Rfc3339,
+    /// Integer block height, only for builders counting time in `TimeUnit::Blocks`.
+    BlockHeight,
+}
+
+impl TimestampFormat {
+    /// Fails if timestamps in this format cannot be expressed in `unit`.
+    pub fn check_unit(self, unit: TimeUnit) -> anyhow::Result<()> {
+        let blocks = matches!(unit, TimeUnit::Blocks { .. });
+        if blocks != (self == TimestampFormat::BlockHeight) {
+            bail!("{self:?} timestamps cannot be stored in {unit:?}");
+        }
+        Ok(())
+    }
+
+    /// Timestamp in `unit`, `None` if the field is malformed, before the epoch or too large.
+    /// Sub-second precision is truncated when stored in seconds.
+    pub fn parse(self, field: &str, unit: TimeUnit) -> Option<u64> {
+        let millis = match self {
+            TimestampFormat::EpochSecs => field.parse::<u64>().ok()?.checked_mul(1000)?,
+            TimestampFormat::EpochMillis => field.parse().ok()?,
+            TimestampFormat::Rfc3339 => u64::try_from(rfc3339_millis(field)?).ok()?,
+            TimestampFormat::BlockHeight => return field.parse().ok(),
+        };
+        match unit {
+            TimeUnit::Seconds => Some(millis / 1000),
+            TimeUnit::Milliseconds => Some(millis),
+            TimeUnit::Blocks { .. } => None,
+        }
+    }
+}
+
+// Milliseconds since the epoch of an RFC 3339 date and time.
+fn rfc3339_millis(text: &str) -> Option<i64> {
+    let b = text.as_bytes();
+    if b.len() < 20
+        || b[4] != b'-'
+        || b[7] != b'-'
+        || !matches!(b[10], b'T' | b't' | b' ')
+        || b[13] != b':'
+        || b[16] != b':'
+    {
+        return None;
+    }
+    let number = |from: usize, to: usize| -> Option<i64> {
+        let digits = text.get(from..to)?;
+        digits
+            .bytes()
+            .all(|c| c.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    };
+    let (year, month, day) = (number(0, 4)?, number(5, 7)?, number(8, 10)?);
+    let (hour, minute, second) = (number(11, 13)?, number(14, 16)?, number(17, 19)?);
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let mut rest = &text[19..];
+    let mut millis = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(|c| c.is_ascii_digit()).count();
+        if len == 0 {
+            return None;
+        }
+        millis = format!("{:0<3}", &fraction[..len.min(3)]).parse().ok()?;
+        rest = &fraction[len..];
+    }
+    let rest = rest.strip_prefix(' ').unwrap_or(rest);
+    let offset_minutes = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), ..] => {
+            let offset = rest[1..].replacen(':', "", 1);
+            let (hours, minutes) = (offset.get(..2)?, offset.get(2..)?);
+            if offset.len() != 4 || !offset.bytes().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            let (hours, minutes) = (hours.parse::<i64>().ok()?, minutes.parse::<i64>().ok()?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 60 + minutes;
+            if *sign == b'-' { -offset } else { offset }
+        }
+        _ => return None,
+    };
+
+    let secs = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second
+        - offset_minutes * 60;
+    Some(secs * 1000 + millis)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc3339() {
+        let parse = |text| TimestampFormat::Rfc3339.parse(text, TimeUnit::Milliseconds);
+        assert_eq!(Some(0), parse("1970-01-01T00:00:00Z"));
+        assert_eq!(
+            Some(1_709_292_600_250),
+            parse("2024-03-01T12:30:00.25+01:00")
+        );
+        assert_eq!(
+            parse("2024-03-01T11:30:00.250Z"),
+            parse("2024-03-01 12:30:00.250 +0100")
+        );
+        assert_eq!(
+            parse("2024-02-29T23:00:00Z"),
+            parse("2024-02-29T20:00:00-03:00")
+        );
+        assert_eq!(
+            Some(1_709_292_600),
+            TimestampFormat::Rfc3339.parse("2024-03-01T12:30:00.999+01:00", TimeUnit::Seconds)
+        );
+        for malformed in [
+            "2024-03-01T12:30:00",
+            "2023-02-29T00:00:00Z",
+            "2024-03-01T24:00:00Z",
+            "2024-03-01T12:30:00+1:00",
+            "2024-03-01T12:30:00.Z",
+            "1969-12-31T23:59:59Z",
+            "2024-03-01",
+        ] {
+            assert_eq!(None, parse(malformed), "{malformed}");
+        }
+    }
+
+    #[test]
+    fn test_epoch_units() {
+        let secs = TimestampFormat::EpochSecs;
+        let millis = TimestampFormat::EpochMillis;
+        assert_eq!(Some(1_500), secs.parse("1500", TimeUnit::Seconds));
+        assert_eq!(Some(1_500_000), secs.parse("1500", TimeUnit::Milliseconds));
+        assert_eq!(Some(1), millis.parse("1999", TimeUnit::Seconds));
+        assert_eq!(None, secs.parse(&u64::MAX.to_string(), TimeUnit::Seconds));
+        assert_eq!(None, millis.parse("-1", TimeUnit::Milliseconds));
+
+        let blocks = TimeUnit::Blocks { block_secs: 12 };
+        assert_eq!(
+            Some(19_000_000),
+            TimestampFormat::BlockHeight.parse("19000000", blocks)
+        );
+        assert!(TimestampFormat::BlockHeight.check_unit(blocks).is_ok());
+        assert!(
+            TimestampFormat::BlockHeight
+                .check_unit(TimeUnit::Seconds)
+                .is_err()
+        );
+        assert!(secs.check_unit(blocks).is_err());
+    }
+}
@@ -1,11 +1,82 @@
-use crate::core::memory::estimate_edge_memory;
+use crate::analysis::label_propagation::label_propagation;
+use crate::analysis::mixer::{MixerConfig, compute_degree_stats, detect_mixers};
+use crate::analysis::taint;
+use crate::core::graph::{FreezeConfig, GraphBuilder};
+use crate::core::ids::NodeRegistry;
+use crate::core::memory::predict_freeze_peak;
+use crate::ingest::csv::ingest_csv;
 use crate::ingest::synthetic::{SyntheticConfig, generate};
+use anyhow::Context;
+use std::fs::File;
 
 pub mod analysis;
 pub mod core;
 pub mod ingest;
 
-fn main() {
+const LABEL_PROPAGATION_ITERS: usize = 10;
+const TAINT_HOPS: usize = 5;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    match args.next() {
+        Some(path) => analyze(&path, args.next().as_deref()),
+        None => {
+            predict_synthetic();
+            Ok(())
+        }
+    }
+}
+
+// Prints the mixers of a CSV ledger, and the taint of every address reached from `source`.
+fn analyze(path: &str, source: Option<&str>) -> anyhow::Result<()> {
+    let mut builder = GraphBuilder::growable();
+    let mut registry = NodeRegistry::new();
+    let file = File::open(path).with_context(|| format!("failed to open {path}"))?;
+    let stats = ingest_csv(file, &mut builder, &mut registry)?;
+    builder.reserve_nodes(registry.len());
+    let graph = builder.try_freeze_with(&FreezeConfig {
+        sort_by_time: true,
+        ..Default::default()
+    })?;
+    println!(
+        "parsed: {}, skipped: {}, nodes: {}",
+        stats.parsed,
+        stats.skipped,
+        registry.len()
+    );
+    for (reason, count) in &stats.rejected {
+        println!("rejected: {reason:?} {count}");
+    }
+
+    let labels = label_propagation(&graph, LABEL_PROPAGATION_ITERS);
+    let degree_stats = compute_degree_stats(&graph);
+    for signal in detect_mixers(&MixerConfig::default(), &graph, &labels, &degree_stats) {
+        if signal.is_mixer {
+            println!(
+                "mixer: {} (score {})",
+                registry.external_id(signal.node),
+                signal.score
+            );
+        }
+    }
+
+    if let Some(source) = source {
+        let start = registry
+            .get(source)
+            .with_context(|| format!("unknown address {source}"))?;
+        let mut risk = taint::propagate(&graph, start, TAINT_HOPS)?
+            .risk
+            .into_iter()
+            .collect::<Vec<_>>();
+        risk.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (node, risk) in risk {
+            println!("taint: {} {risk:.6}", registry.external_id(node));
+        }
+    }
+    Ok(())
+}
+
+fn predict_synthetic() {
     let cfg = SyntheticConfig {
         node_count: 1_000_000,
         edge_count: 10_000_000,
@@ -14,11 +85,15 @@ fn main() {
 
     let edge_count = generate(&cfg).count();
 
-    let stats = estimate_edge_memory(edge_count);
+    let bytes = predict_freeze_peak(
+        cfg.node_count as usize,
+        edge_count,
+        &FreezeConfig::default(),
+    );
 
     println!(
-        "edges: {}, approx memory: {} MB",
-        stats.edges,
-        stats.bytes / (1024 * 1024)
+        "edges: {}, predicted peak memory: {} MB",
+        edge_count,
+        bytes / (1024 * 1024)
     );
 }